@@ -0,0 +1,74 @@
+use std::error::Error;
+
+use search::{Search, CharInfo, QError, literal_query};
+use suggest;
+
+/// What the popup shows for a search query, worked out apart from the window so it can be tested. Corrections
+/// for queries that find nothing are left out, they take long enough to be looked for once typing stops.
+#[derive(Debug, Default)]
+pub struct Answer {
+    /// the first of the results, as many as fit
+    pub chars: Vec<CharInfo>,
+    /// how many results there are in all
+    pub total: usize,
+    /// the rest of the word being typed, for Tab to accept
+    pub completion: Option<String>,
+    /// why the query couldn't be parsed, in which case `chars` are what its words find as they are
+    pub parse_error: Option<QError>
+}
+
+/// Search for `query`, keeping at most `limit` results, and complete its last word when the cursor is `at_end` of it
+pub fn answer(search: &Search, query: &str, limit: usize, at_end: bool) -> Result<Answer, Box<dyn Error>> {
+    let mut answer = Answer::default();
+    let page = match search.query_page(query, 0, limit) {
+        Ok(page) => page,
+        Err(e) => {
+            // what can't be parsed is pointed out, and the words in it are searched for as they are
+            answer.parse_error = Some(*e.downcast::<QError>()?);
+            let page = search.query_page(&literal_query(query), 0, limit)?;
+            answer.chars = page.chars;
+            answer.total = page.total;
+            return Ok(answer);
+        }
+    };
+    if at_end {
+        answer.completion = suggest::complete(search, query).unwrap_or(None);
+    }
+    answer.chars = page.chars;
+    answer.total = page.total;
+    Ok(answer)
+}
+
+#[cfg(test)]
+mod tests {
+    use ::answer::*;
+    use ::search::tests::fixture;
+
+    const CHARS: &str = r#"<group blk="Arrows" gc="Sm" sc="Zyyy" bc="ON">
+<char cp="21D2" na="RIGHTWARDS DOUBLE ARROW"/>
+</group>
+"#;
+
+    fn cps(answer: &Answer) -> Vec<u32> {
+        answer.chars.iter().map(|c| c.cp).collect()
+    }
+
+    #[test]
+    fn finds_and_completes() {
+        let s = fixture(CHARS);
+        let a = answer(&s, "rightwards arr", 1, true).unwrap();
+        assert_eq!((cps(&a), a.total), (vec![0x2192], 3));
+        assert_eq!(a.completion, Some("ow".to_owned()));
+        assert!(a.parse_error.is_none());
+        // only the word at the cursor is completed
+        assert_eq!(answer(&s, "rightwards arr", 1, false).unwrap().completion, None);
+    }
+
+    #[test]
+    fn searches_words_of_broken_queries() {
+        let s = fixture(CHARS);
+        let a = answer(&s, "(double arrow", 10, true).unwrap();
+        assert_eq!(a.parse_error.as_ref().map(|e| e.position), Some(Some(0)));
+        assert_eq!(cps(&a)[0], 0x21d2);
+    }
+}
@@ -1,18 +1,23 @@
 
-use std::fs;
-use std::fs::{File, OpenOptions};
-use std::io::BufReader;
 use std::error::Error;
-use std::io::{Cursor, ErrorKind as IOErrorKind, copy, Seek, SeekFrom, Read, Write};
-
-use tantivy::{Index, IndexReader};
-use tantivy::schema::*;
-use tantivy::collector::TopDocs;
-use tantivy::query::QueryParser;
-
-use xml::name::OwnedName;
-use xml::attribute::OwnedAttribute;
-use xml::reader::{EventReader, XmlEvent};
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+
+use search::{Search, CharInfo, QError, literal_query};
+use editor::{QueryEditor, ComposeBuffer};
+use history::History;
+use confusables::Confusables;
+use describe;
+use normalize::{Normalizer, Form, FORMS, Change, diff};
+use styles::Styles;
+use compose::{self, Composer};
+use formats::{Format, FORMATS};
+use detail::Detail;
+use browse::{self, Browser};
+use grid::{Grid, Move};
+use emoji::Emojis;
+use answer;
+use suggest;
 
 use vgu::*;
 
@@ -39,24 +44,6 @@ use toml::Value as TomlValue;
  * ✓ Cursor in search box (maybe a magnifying glass to hint that's the search box too?)
  */
 
-#[derive(Debug)]
-struct TError(tantivy::TantivyError);
-
-impl std::error::Error for TError {}
-
-impl std::fmt::Display for TError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
-        self.0.fmt(f)
-    }
-}
-
-impl From<tantivy::TantivyError> for TError {
-    fn from(e: tantivy::TantivyError) -> Self {
-        TError(e)
-    }
-}
-
-
 /*#[repr(C)] #[derive(Clone,Copy)]
 #[allow(non_snake_case)]
 struct GUITHREADINFO {
@@ -79,82 +66,50 @@ pub struct App {
     rt: WindowRenderTarget,
     b: Brush, sel_b: Brush,
     txf: TextFactory,
-    fnt: Font,
-    query_string: String,
+    fnt: Font, big_fnt: Font, grid_fnt: Font,
+    query: QueryEditor,
+    /// what Shift+Enter has picked so far, shown above the query and inserted along with the next pick
+    buffer: ComposeBuffer,
     sel_char: usize, res_window: usize,
-    cursor: usize,
 
-    namef: Field, blckf: Field, cpnf: Field,
-    index: Index, index_reader: IndexReader, 
-    qpar: QueryParser, 
-    last_query: Option<Vec<Document>>,
+    search: Search,
+    last_query: Option<Vec<CharInfo>>,
+    /// how many characters match the search in all, when the results are its first pages and more can be fetched
+    total_hits: Option<usize>,
+    history: History,
+    /// loaded the first time confusables are asked for, since it may have to be downloaded
+    confusables: Option<Confusables>,
+    /// what `=text` is converted to before it is shown, and which of the shown codepoints the conversion added
+    form: Option<Form>, added: Vec<bool>,
+    /// built from the index the first time `~text` is typed, and the rows it shows in place of the results
+    styles: Option<Styles>, styled: Option<Vec<(&'static str, String)>>,
+    /// the base and marks being put together, which the results are limited to marks for, and how it looks composed
+    composer: Option<Composer>, preview: String,
+    /// how the picked text is inserted; Ctrl+O changes it until the next insert, which goes back to the configured one
+    format: Format, default_format: Format,
+    /// whether the detail pane is shown in place of the results, and the character it was last worked out for
+    show_detail: bool, detail: Option<(u32, Detail)>,
+    /// browsing by block, script or category, and which of the groups match the query while none is open
+    browser: Option<Browser>, browse_rows: Option<Vec<usize>>,
+    /// whether the results are shown as a grid of glyphs instead of a list, and its size
+    grid: Grid, grid_view: bool,
+    /// loaded the first time Ctrl+E is pressed, the group whose tab is open, and the emoji in it that match the query
+    emojis: Option<Emojis>, emoji_tab: Option<usize>, emoji_rows: Option<Vec<usize>>,
+    /// where the emoji arrive from the thread downloading them, so the popup isn't stuck while it does
+    emoji_loading: Option<Receiver<Result<Emojis, String>>>,
+    /// the rest of the word being typed, shown after the cursor for Tab to accept,
+    /// and corrected queries offered in place of the results when the search finds nothing
+    completion: Option<String>, suggestions: Vec<String>,
+    /// why the query couldn't be parsed, while its words are searched for as they are instead
+    parse_error: Option<QError>,
+    /// why data a key needs couldn't be loaded or the results couldn't be found, shown until the query changes
+    load_error: Option<String>,
 
     foreground_window: Option<HWND>, ctrl_pressed: bool,
 
     background_color: D2D1_COLOR_F
 }
 
-fn build_index(schema: &Schema, index: &Index) -> Result<(), Box<dyn Error>> {
-    let namef = schema.get_field("name").unwrap();
-    let blckf = schema.get_field("blck").unwrap();
-    let cpnf  = schema.get_field("codepnt").unwrap();
-
-    let mut ixw = index.writer(50_000_000).map_err(TError)?;
-    let fbr = BufReader::new(match File::open("./ucd.nounihan.grouped.xml") {
-        Ok(f) => f,
-        Err(e) => match e.kind() {
-            IOErrorKind::NotFound => {
-                // download latest UCD xml
-                use curl::easy::Easy as Curl;
-                let mut curl = Curl::new();
-                let mut buffer = Vec::new();
-                curl.url("http://www.unicode.org/Public/UCD/latest/ucdxml/ucd.nounihan.grouped.zip")?;
-                {
-                    let mut transfer = curl.transfer(); 
-                    transfer.write_function(|data| { buffer.extend_from_slice(data); Ok(data.len()) })?;
-                    transfer.perform()?;
-                }
-                use zip::read::*;
-                let mut zip = ZipArchive::new(Cursor::new(&buffer))?;
-                let mut archf = zip.by_index(0)?; // Unicode archives only have 1 file in them
-                let mut f = OpenOptions::new().read(true).write(true).truncate(true).create(true).open("./ucd.nounihan.grouped.xml")?;
-                copy(&mut archf, &mut f)?;
-                f.seek(SeekFrom::Start(0))?;
-                f
-            },
-            _ => return Err(Box::new(e))
-        }
-    });//.expect("opening Unicode XML data");
-    let parser = EventReader::new(fbr);
-    let mut current_block_name = String::new();
-    let defatb = OwnedAttribute::new(OwnedName::local("NONE"), "0");
-    for e in parser {
-        match e {
-            Ok(XmlEvent::StartElement { name, attributes: atrib, .. }) => {
-                match name.local_name.as_str() {
-                    "group" => {
-                        current_block_name = atrib.iter().find(|&a| a.name.local_name == "blk").unwrap_or(&defatb).value.clone();
-                        println!("processing {}", current_block_name);
-                    },
-                    "char" => {
-                        let mut doc = Document::default();
-                        doc.add_text(blckf, current_block_name.as_str());
-                        let mut itr = atrib.iter();
-                        doc.add_u64(cpnf, u64::from_str_radix(itr.find(|&a| a.name.local_name == "cp").unwrap_or(&defatb).value.as_str(), 16).unwrap());
-                        doc.add_text(namef, itr.find(|&a| a.name.local_name == "na" || a.name.local_name == "na1").unwrap_or(&defatb).value.as_str());
-                        ixw.add_document(doc);
-                    }
-                    _ => {}
-                }
-            },
-            Err(e) => { return Err(Box::new(e)); },
-            _ => {}
-        }
-    }
-    ixw.commit().map_err(TError)?;//.expect("commiting index changed");
-    Ok(())
-}
-
 fn color_from_value(tv: &TomlValue, a: f32) -> Option<D2D1_COLOR_F> {
     tv.as_array().and_then(|v|
         v[0].as_float()
@@ -163,20 +118,59 @@ fn color_from_value(tv: &TomlValue, a: f32) -> Option<D2D1_COLOR_F> {
                                                 .map(|b| D2D1_COLOR_F{r: r as f32, g: g as f32, b: b as f32, a: a}))))
 }
 
-const INDEX_VERSION: u32 = 5;
 const VISIBLE_ITEMS: usize = 20;
 
+/// Starting the query with this shows what the rest of it is made of instead of searching
+const DESCRIBE_PREFIX: char = '=';
+/// Starting the query with this shows the rest of it in every style
+const STYLE_PREFIX: char = '~';
+
+/// Put `text` on the clipboard, `owner` must be a window belonging to this process
+unsafe fn set_clipboard_text(owner: HWND, text: &str) {
+    let text16 = text.encode_utf16().chain(Some(0)).collect::<Vec<u16>>();
+    OpenClipboard(owner);
+    EmptyClipboard();
+    let global_text = GlobalAlloc(0x0042, text16.len()*2);
+    let tcopy = GlobalLock(global_text) as *mut u16;
+    ::std::ptr::copy_nonoverlapping(text16.as_ptr(), tcopy, text16.len());
+    GlobalUnlock(global_text);
+    SetClipboardData(CF_UNICODETEXT, global_text);
+    CloseClipboard();
+}
+
+/// Press Ctrl+V in the foreground window
+unsafe fn send_paste() {
+    keybd_event(VK_CONTROL as u8, 0, 0, 0);
+    keybd_event(b'V', 0, 0, 0);
+    keybd_event(b'V', 0, KEYEVENTF_KEYUP, 0);
+    keybd_event(VK_CONTROL as u8, 0, KEYEVENTF_KEYUP, 0);
+}
+
+/// Paste `text` into whatever window has focus. This is for callers without a window of their
+/// own like the daemon, so a message-only window is made to own the clipboard.
+pub fn paste_text(text: &str) -> Result<(), Box<dyn Error>> {
+    unsafe {
+        let class = "STATIC".encode_utf16().chain(Some(0)).collect::<Vec<u16>>();
+        let owner = CreateWindowExW(0, class.as_ptr(), [0u16].as_ptr(), 0, 0, 0, 0, 0,
+                                    HWND_MESSAGE, null_mut(), null_mut(), null_mut());
+        if owner.is_null() {
+            return Err(Box::new(HResultError::last_win32_error()));
+        }
+        set_clipboard_text(owner, text);
+        send_paste();
+        DestroyWindow(owner);
+    }
+    Ok(())
+}
+
 impl App {
     pub fn new(config: &Option<TomlValue>) -> Result<App, Box<dyn Error>> {
-println!("171");
         let fac = Factory::new()?;//.expect("creating Direct2D factory");
-        println!("173");
         let mut dpi: (f32, f32) = (0.0, 0.0);
         unsafe { fac.GetDesktopDpi(&mut dpi.0, &mut dpi.1); }
         let win = Window::new((((520.0) * (dpi.0 / 96.0)).ceil() as i32,
                 ((520.0) * (dpi.1 / 96.0)).ceil() as i32), Some(winproc))?;//.expect("creating window");
         let rt = WindowRenderTarget::new(fac.clone(), &win)?;//.expect("creating HwndRenderTarget");
-println!("177");
         let (main_color, sel_color, bg_color) = {
             let colors = config.as_ref().and_then(|c| c.get("colors"));
             (colors.as_ref().and_then(|c| c.get("main"))
@@ -192,67 +186,27 @@ println!("177");
         let b = Brush::solid_color(rt.clone(), main_color)?;//.expect("creating solid color brush");
         let sel_b = Brush::solid_color(rt.clone(), sel_color)?;//.expect("creating solid color brush");
         let txf = TextFactory::new().expect("creating DWrite factory");
-        let fnt = Font::new(txf.clone(), String::from(config.as_ref().and_then(|c| c.get("font")).and_then(|v| v.as_str()).unwrap_or("Consolas")), 
-                            DWRITE_FONT_WEIGHT_NORMAL, DWRITE_FONT_STYLE_NORMAL, 16.0)?;//.expect("creating font");
-        let mut schb = SchemaBuilder::default();
-        let namef = schb.add_text_field("name", TEXT | STORED);
-        let blckf = schb.add_text_field("blck", TEXT | STORED);
-        let cpnf = schb.add_u64_field("codepnt", STORED);
-        let schema = schb.build();
-        match fs::OpenOptions::new().read(true).write(true).open("./index_version") {
-            Ok(mut f) => {
-                let mut s = String::new();
-                f.read_to_string(&mut s)?;
-                if s.parse::<u32>()? < INDEX_VERSION {
-                    match fs::remove_dir_all("./index") {
-                        Ok(()) => {},
-                        Err(e) => match e.kind() {
-                            IOErrorKind::NotFound => {},
-                            _ => return Err(Box::new(e))
-                        }
-                    }
-                    write!(f, "{}", INDEX_VERSION)?;
-                }
-            },
-            Err(e) => match e.kind() {
-                IOErrorKind::NotFound => {
-                    let mut f = File::create("./index_version")?;
-                    write!(f, "{}", INDEX_VERSION)?;
-                    match fs::remove_dir_all("./index") {
-                        Ok(()) => {},
-                        Err(e) => match e.kind() {
-                            IOErrorKind::NotFound => {},
-                            _ => return Err(Box::new(e))
-                        }
-                    }
-                }
-                _ => return Err(Box::new(e))
-            }
-        };
-        let index = match tantivy::directory::MmapDirectory::open("./index").map_err(tantivy::TantivyError::from).map(|dir| Index::open(dir)) {
-            Ok(ix) => ix.map_err(TError)?,
-            Err(tantivy::TantivyError::PathDoesNotExist(_)) => {
-                println!("x");
-                fs::create_dir("./index")?;
-                let ix = Index::create(tantivy::directory::MmapDirectory::open("./index")?, schema.clone()).map_err(TError)?;
-                if let Err(e) = build_index(&schema, &ix) {
-                    fs::remove_dir_all("./index")?;
-                    return Err(e);
-                }
-                ix
-            },
-            Err(e) => {
-                println!("y");
-                return Err(Box::new(TError(e)));//panic!("creating index: {:?}", e);
-            }
+        let font_name = String::from(config.as_ref().and_then(|c| c.get("font")).and_then(|v| v.as_str()).unwrap_or("Consolas"));
+        let fnt = Font::new(txf.clone(), font_name.clone(), DWRITE_FONT_WEIGHT_NORMAL, DWRITE_FONT_STYLE_NORMAL, 16.0)?;//.expect("creating font");
+        let big_fnt = Font::new(txf.clone(), font_name.clone(), DWRITE_FONT_WEIGHT_NORMAL, DWRITE_FONT_STYLE_NORMAL, 64.0)?;
+        let grid_fnt = Font::new(txf.clone(), font_name, DWRITE_FONT_WEIGHT_NORMAL, DWRITE_FONT_STYLE_NORMAL, 24.0)?;
+        let (grid, grid_view) = Grid::from_config(config);
+        let default_format = match config.as_ref().and_then(|c| c.get("format")).and_then(|v| v.as_str()) {
+            Some(f) => Format::parse(f).ok_or_else(|| format!("unknown format {} in config", f))?,
+            None => Format::Raw
         };
-        let ixreader = index.reader().map_err(TError)?;
+        let search = Search::open(config)?;
+        let history = History::open("./history")?;
         Ok(App {
-            win, factory: fac, rt, b, sel_b, txf, fnt, query_string: String::from(""), sel_char: 0, cursor: 0, res_window: 0,
-            namef, blckf, cpnf, index: index, index_reader: ixreader,
-            qpar: QueryParser::new(schema.clone(), vec![namef, blckf], ::tantivy::tokenizer::TokenizerManager::default()),
+            win, factory: fac, rt, b, sel_b, txf, fnt, big_fnt, grid_fnt, query: QueryEditor::new(), buffer: ComposeBuffer::default(), sel_char: 0, res_window: 0,
+            search, history, confusables: None, form: None, added: Vec::new(),
+            styles: None, styled: None, composer: None, preview: String::new(),
+            format: default_format, default_format, show_detail: false, detail: None,
+            browser: None, browse_rows: None, grid, grid_view,
+            emojis: None, emoji_tab: None, emoji_rows: None, emoji_loading: None,
+            completion: None, suggestions: Vec::new(), parse_error: None, load_error: None,
             background_color: bg_color,
-            last_query: None, foreground_window: None, ctrl_pressed: false
+            last_query: None, total_hits: None, foreground_window: None, ctrl_pressed: false
         })
     }
 
@@ -267,28 +221,105 @@ println!("177");
         }
 
         // draw the query 'textbox'
-        let query_layout = TextLayout::new(self.txf.clone(), &self.query_string, &self.fnt, 512.0, 32.0).expect("create query string layout");
         let mut r = D2D1_RECT_F{left: 8.0, right:512.0, top:8.0, bottom:32.0};
+        if !self.buffer.is_empty() {
+            let buffer16 = self.buffer.text().encode_utf16().collect::<Vec<u16>>();
+            self.rt.DrawText(buffer16.as_ptr(), buffer16.len() as u32, self.fnt.p, &r, self.sel_b.p,
+                             D2D1_DRAW_TEXT_OPTIONS_ENABLE_COLOR_FONT | D2D1_DRAW_TEXT_OPTIONS_CLIP, winapi::um::dcommon::DWRITE_MEASURING_MODE_NATURAL);
+            r.top += 24.0; r.bottom += 24.0;
+        }
+        let query_layout = TextLayout::new(self.txf.clone(), self.query.text(), &self.fnt, 512.0, 32.0).expect("create query string layout");
         self.rt.DrawRectangle(&r, self.b.p, 1.0, null_mut());
         r.left += 2.0; r.top += 2.0;
         self.rt.DrawTextLayout(D2D1_POINT_2F{x: r.left, y: r.top}, query_layout.p, self.b.p, D2D1_DRAW_TEXT_OPTIONS_ENABLE_COLOR_FONT);
-        let mut cb = query_layout.char_bounds(self.cursor);
+        let mut cb = query_layout.char_bounds(self.query.cursor_utf16());
         cb.left += r.left; cb.top += r.top;
         cb.right += r.left; cb.bottom += r.top;
         if cb.left == cb.right { cb.right += 8.0; }
+        if let Some(ref rest) = self.completion {
+            // greyed out after the cursor, which is at the end of the query
+            let rest16 = rest.encode_utf16().collect::<Vec<u16>>();
+            let cr = D2D1_RECT_F{left: cb.left, right: 446.0, top: cb.top, bottom: r.bottom};
+            self.rt.DrawText(rest16.as_ptr(), rest16.len() as u32, self.fnt.p, &cr, self.sel_b.p,
+                             D2D1_DRAW_TEXT_OPTIONS_CLIP, winapi::um::dcommon::DWRITE_MEASURING_MODE_NATURAL);
+        }
         self.rt.FillRectangle(&cb, self.sel_b.p);
+        if let Some(position) = self.parse_error.as_ref().and_then(|e| e.position) {
+            // underline where the query goes wrong
+            let at = self.query.text().chars().take(position).map(char::len_utf16).sum::<usize>();
+            let eb = query_layout.char_bounds(at);
+            let y = r.top + eb.bottom;
+            self.rt.DrawLine(D2D1_POINT_2F{x: r.left + eb.left, y}, D2D1_POINT_2F{x: r.left + eb.right.max(eb.left + 8.0), y},
+                             self.sel_b.p, 2.0, null_mut());
+        }
+        let describing = self.query.text().starts_with(DESCRIBE_PREFIX);
+        if let (true, Some(form)) = (describing, self.form) {
+            let label = form.name().encode_utf16().collect::<Vec<u16>>();
+            let lr = D2D1_RECT_F{left: 448.0, right: 510.0, top: r.top, bottom: r.bottom};
+            self.rt.DrawText(label.as_ptr(), label.len() as u32, self.fnt.p, &lr, self.sel_b.p,
+                             D2D1_DRAW_TEXT_OPTIONS_CLIP, winapi::um::dcommon::DWRITE_MEASURING_MODE_NATURAL);
+        }
+
+        if let Some(total) = self.total_hits {
+            let label = format!("{}/{}", (self.sel_char + 1).min(total), total).encode_utf16().collect::<Vec<u16>>();
+            let lr = D2D1_RECT_F{left: 448.0, right: 510.0, top: r.top, bottom: r.bottom};
+            self.rt.DrawText(label.as_ptr(), label.len() as u32, self.fnt.p, &lr, self.sel_b.p,
+                             D2D1_DRAW_TEXT_OPTIONS_CLIP, winapi::um::dcommon::DWRITE_MEASURING_MODE_NATURAL);
+        }
 
+        if self.format != Format::Raw {
+            let label = self.format.name().encode_utf16().collect::<Vec<u16>>();
+            let lr = D2D1_RECT_F{left: 370.0, right: 446.0, top: r.top, bottom: r.bottom};
+            self.rt.DrawText(label.as_ptr(), label.len() as u32, self.fnt.p, &lr, self.sel_b.p,
+                             D2D1_DRAW_TEXT_OPTIONS_CLIP, winapi::um::dcommon::DWRITE_MEASURING_MODE_NATURAL);
+        }
 
         // draw the query results
         r.top += 28.0; r.bottom += 28.0;
+        if let (Some(emojis), Some(tab)) = (&self.emojis, self.emoji_tab) {
+            self.paint_emoji_tabs(emojis, tab, r);
+            r.top += 32.0; r.bottom += 32.0;
+        }
+        if let Some(ref error) = self.parse_error {
+            let message16 = format!("{}, searching for the words", error.message).encode_utf16().collect::<Vec<u16>>();
+            self.rt.DrawText(message16.as_ptr(), message16.len() as u32, self.fnt.p, &r, self.sel_b.p,
+                             D2D1_DRAW_TEXT_OPTIONS_CLIP, winapi::um::dcommon::DWRITE_MEASURING_MODE_NATURAL);
+            r.top += 24.0; r.bottom += 24.0;
+        }
+        if let Some(ref error) = self.load_error {
+            let message16 = error.encode_utf16().collect::<Vec<u16>>();
+            self.rt.DrawText(message16.as_ptr(), message16.len() as u32, self.fnt.p, &r, self.sel_b.p,
+                             D2D1_DRAW_TEXT_OPTIONS_CLIP, winapi::um::dcommon::DWRITE_MEASURING_MODE_NATURAL);
+            r.top += 24.0; r.bottom += 24.0;
+        }
+        if self.composer.is_some() {
+            let preview16 = self.preview.encode_utf16().collect::<Vec<u16>>();
+            self.rt.DrawText(preview16.as_ptr(), preview16.len() as u32, self.fnt.p, &r, self.sel_b.p,
+                             D2D1_DRAW_TEXT_OPTIONS_ENABLE_COLOR_FONT | D2D1_DRAW_TEXT_OPTIONS_CLIP, winapi::um::dcommon::DWRITE_MEASURING_MODE_NATURAL);
+            r.top += 24.0; r.bottom += 24.0;
+        }
         match self.last_query {
-            Some(ref das) => {
-                let sel_char = self.sel_char;
-                for (rd,sel) in das.iter().zip((0..).map(|i| i == sel_char)).skip(self.res_window).take(VISIBLE_ITEMS) {
-                    let cp = rd.get_first(self.cpnf).unwrap().u64_value();
-                    let entry = format!("{}: {} - {}", ::std::char::from_u32(cp as u32).unwrap_or(' '),
-                            rd.get_first(self.namef).unwrap().text().unwrap_or("!"),
-                            rd.get_first(self.blckf).unwrap().text().unwrap_or("!"));
+            Some(ref das) if das.is_empty() && !self.suggestions.is_empty() => {
+                for (i, suggestion) in self.suggestions.iter().enumerate() {
+                    let entry16 = format!("did you mean: {}", suggestion).encode_utf16().collect::<Vec<u16>>();
+                    self.rt.DrawText(entry16.as_ptr(), entry16.len() as u32,
+                                     self.fnt.p, &r, self.sel_b.p, D2D1_DRAW_TEXT_OPTIONS_CLIP, winapi::um::dcommon::DWRITE_MEASURING_MODE_NATURAL);
+                    if i == self.sel_char { self.rt.DrawRectangle(&r, self.sel_b.p, 1.0, null_mut()); }
+                    r.top += 24.0; r.bottom += 24.0;
+                }
+            },
+            Some(ref das) if !self.show_detail && !self.grid_active() => {
+                for (i, rd) in das.iter().enumerate().skip(self.res_window).take(VISIBLE_ITEMS) {
+                    let sel = i == self.sel_char;
+                    let entry = if describing {
+                        format!("{}{}", if self.added.get(i) == Some(&true) { "+" } else { " " }, describe::format_row(rd))
+                    } else if compose::is_mark(rd) {
+                        // marks go on the base being composed, or a dotted circle, instead of a space
+                        let base = self.composer.as_ref().map_or(String::from("\u{25cc}"), Composer::text);
+                        format!("{}{}: {} - {}", base, rd.chr().unwrap_or(' '), rd.name, rd.block)
+                    } else {
+                        format!("{}: {} - {}", rd.chr().unwrap_or(' '), rd.name, rd.block)
+                    };
                     let entry16 = entry.encode_utf16().collect::<Vec<u16>>();
                     self.rt.DrawText(entry16.as_ptr(), entry16.len() as u32,
                                      self.fnt.p, &r, self.b.p, D2D1_DRAW_TEXT_OPTIONS_ENABLE_COLOR_FONT | D2D1_DRAW_TEXT_OPTIONS_CLIP, winapi::um::dcommon::DWRITE_MEASURING_MODE_NATURAL);
@@ -297,7 +328,49 @@ println!("177");
                     r.top += 24.0; r.bottom += 24.0;
                 }
             }, 
-            None => {} 
+            _ => {} 
+        }
+        if self.show_detail {
+            self.update_detail();
+            self.paint_detail(r);
+        }
+        if self.grid_active() {
+            self.paint_grid(r);
+        }
+        if let Some(ref rows) = self.styled {
+            for (i, (name, text)) in rows.iter().enumerate().skip(self.res_window).take(VISIBLE_ITEMS) {
+                let entry16 = format!("{:<22} {}", name, text).encode_utf16().collect::<Vec<u16>>();
+                self.rt.DrawText(entry16.as_ptr(), entry16.len() as u32,
+                                 self.fnt.p, &r, self.b.p, D2D1_DRAW_TEXT_OPTIONS_ENABLE_COLOR_FONT | D2D1_DRAW_TEXT_OPTIONS_CLIP, winapi::um::dcommon::DWRITE_MEASURING_MODE_NATURAL);
+                if i == self.sel_char { self.rt.DrawRectangle(&r, self.sel_b.p, 1.0, null_mut()); }
+                r.top += 24.0; r.bottom += 24.0;
+            }
+        }
+        if let (Some(rows), Some(browser)) = (&self.browse_rows, &self.browser) {
+            for (i, &g) in rows.iter().enumerate().skip(self.res_window).take(VISIBLE_ITEMS) {
+                let entry16 = browser.groups[g].label().encode_utf16().collect::<Vec<u16>>();
+                self.rt.DrawText(entry16.as_ptr(), entry16.len() as u32,
+                                 self.fnt.p, &r, self.b.p, D2D1_DRAW_TEXT_OPTIONS_CLIP, winapi::um::dcommon::DWRITE_MEASURING_MODE_NATURAL);
+                if i == self.sel_char { self.rt.DrawRectangle(&r, self.sel_b.p, 1.0, null_mut()); }
+                r.top += 24.0; r.bottom += 24.0;
+            }
+        }
+        if let (Some(rows), Some(emojis)) = (&self.emoji_rows, &self.emojis) {
+            for (i, &e) in rows.iter().enumerate().skip(self.res_window).take(VISIBLE_ITEMS) {
+                let emoji = &emojis.emoji[e];
+                let entry16 = format!("{}: {}", emoji.text, emoji.name).encode_utf16().collect::<Vec<u16>>();
+                self.rt.DrawText(entry16.as_ptr(), entry16.len() as u32,
+                                 self.fnt.p, &r, self.b.p, D2D1_DRAW_TEXT_OPTIONS_ENABLE_COLOR_FONT | D2D1_DRAW_TEXT_OPTIONS_CLIP, winapi::um::dcommon::DWRITE_MEASURING_MODE_NATURAL);
+                // the subgroup heading goes on the right of its first row, and of the top row
+                if i == self.res_window || emojis.emoji[rows[i - 1]].subgroup != emoji.subgroup {
+                    let heading16 = emojis.subgroups[emoji.subgroup].0.encode_utf16().collect::<Vec<u16>>();
+                    let hr = D2D1_RECT_F{left: 360.0, right: r.right, top: r.top, bottom: r.bottom};
+                    self.rt.DrawText(heading16.as_ptr(), heading16.len() as u32, self.fnt.p, &hr, self.sel_b.p,
+                                     D2D1_DRAW_TEXT_OPTIONS_CLIP, winapi::um::dcommon::DWRITE_MEASURING_MODE_NATURAL);
+                }
+                if i == self.sel_char { self.rt.DrawRectangle(&r, self.sel_b.p, 1.0, null_mut()); }
+                r.top += 24.0; r.bottom += 24.0;
+            }
         }
         if self.ctrl_pressed {
             self.rt.DrawLine(D2D1_POINT_2F{x: 0.0, y:0.0}, D2D1_POINT_2F{x:800.0,y:0.0}, self.sel_b.p, 1.0, null_mut());
@@ -305,18 +378,359 @@ println!("177");
         self.rt.EndDraw(null_mut(), null_mut());
     }
 
+    /// A tab for each emoji group, shown as its first emoji, with the name of the open one after them
+    unsafe fn paint_emoji_tabs(&self, emojis: &Emojis, tab: usize, r: D2D1_RECT_F) {
+        let mut tr = D2D1_RECT_F{left: r.left, right: r.left + 30.0, top: r.top, bottom: r.top + 28.0};
+        for g in 0..emojis.groups.len() {
+            if let Some(first) = emojis.emoji.iter().find(|e| emojis.group_of(e) == g) {
+                let icon16 = first.text.encode_utf16().collect::<Vec<u16>>();
+                self.rt.DrawText(icon16.as_ptr(), icon16.len() as u32, self.fnt.p, &tr, self.b.p,
+                                 D2D1_DRAW_TEXT_OPTIONS_ENABLE_COLOR_FONT | D2D1_DRAW_TEXT_OPTIONS_CLIP, winapi::um::dcommon::DWRITE_MEASURING_MODE_NATURAL);
+            }
+            if g == tab { self.rt.DrawRectangle(&tr, self.sel_b.p, 1.0, null_mut()); }
+            tr.left += 32.0; tr.right += 32.0;
+        }
+        let name16 = emojis.groups[tab].encode_utf16().collect::<Vec<u16>>();
+        let nr = D2D1_RECT_F{left: tr.left + 8.0, right: r.right, top: r.top, bottom: r.top + 28.0};
+        self.rt.DrawText(name16.as_ptr(), name16.len() as u32, self.fnt.p, &nr, self.sel_b.p,
+                         D2D1_DRAW_TEXT_OPTIONS_CLIP, winapi::um::dcommon::DWRITE_MEASURING_MODE_NATURAL);
+    }
+
+    /// Whether the results are being shown as a grid, which the arrow keys move around
+    fn grid_active(&self) -> bool {
+        self.grid_view && !self.show_detail && self.styled.is_none() && self.browse_rows.is_none() && self.last_query.is_some()
+    }
+
+    /// The page of the grid the selection is on, from the top of `r` down, with the selected character's name below it
+    unsafe fn paint_grid(&self, r: D2D1_RECT_F) {
+        let das = match self.last_query { Some(ref das) => das, None => return };
+        let cell = (r.right - r.left) / self.grid.columns as f32;
+        let start = self.grid.page_start(self.sel_char);
+        for (i, rd) in das.iter().enumerate().skip(start).take(self.grid.page_size()) {
+            let (row, col) = ((i - start) / self.grid.columns, (i - start) % self.grid.columns);
+            let cr = D2D1_RECT_F{left: r.left + col as f32 * cell, right: r.left + (col + 1) as f32 * cell,
+                                 top: r.top + row as f32 * cell, bottom: r.top + (row + 1) as f32 * cell};
+            let glyph16 = describe::display_cluster(::std::slice::from_ref(rd)).encode_utf16().collect::<Vec<u16>>();
+            self.rt.DrawText(glyph16.as_ptr(), glyph16.len() as u32, self.grid_fnt.p, &cr, self.b.p,
+                             D2D1_DRAW_TEXT_OPTIONS_ENABLE_COLOR_FONT | D2D1_DRAW_TEXT_OPTIONS_CLIP, winapi::um::dcommon::DWRITE_MEASURING_MODE_NATURAL);
+            if i == self.sel_char { self.rt.DrawRectangle(&cr, self.sel_b.p, 1.0, null_mut()); }
+        }
+        if let Some(rd) = das.get(self.sel_char) {
+            let top = r.top + self.grid.rows as f32 * cell + 4.0;
+            let sr = D2D1_RECT_F{left: r.left, right: r.right, top, bottom: top + 24.0};
+            let status16 = format!("{} - {}  {}/{}", rd.describe(), rd.block, self.sel_char + 1, self.available()).encode_utf16().collect::<Vec<u16>>();
+            self.rt.DrawText(status16.as_ptr(), status16.len() as u32, self.fnt.p, &sr, self.sel_b.p,
+                             D2D1_DRAW_TEXT_OPTIONS_CLIP, winapi::um::dcommon::DWRITE_MEASURING_MODE_NATURAL);
+        }
+    }
+
+    /// How many results to fetch at a time, enough to page through a few screens of the grid
+    fn result_limit(&self) -> usize {
+        if self.grid_view { (self.grid.page_size() * 4).max(40) } else { 40 }
+    }
+
+    /// How many rows there are to select, counting the search results that haven't been fetched yet
+    fn available(&self) -> usize {
+        self.total_hits.unwrap_or(0).max(self.result_count())
+    }
+
+    /// Fetch more of the search results, so there are at least `upto` of them when the search has that many
+    fn load_more(&mut self, upto: usize) {
+        let total = match self.total_hits { Some(total) => total, None => return };
+        let loaded = self.last_query.as_ref().map_or(0, Vec::len);
+        if upto <= loaded || loaded >= total { return; }
+        let query = if self.parse_error.is_some() { literal_query(self.query.text()) } else { self.query.text().to_owned() };
+        if let Ok(page) = self.search.query_page(&query, loaded, (upto - loaded).max(self.result_limit())) {
+            if let Some(ref mut lq) = self.last_query { lq.extend(page.chars); }
+        }
+    }
+
+    /// Move the selection to `sel`, kept inside the results, scrolling the list so it stays in view
+    fn select(&mut self, sel: usize) {
+        // a screen ahead is fetched, so the next move doesn't have to wait
+        let ahead = if self.grid_active() { self.grid.page_size() } else { VISIBLE_ITEMS };
+        self.load_more(sel + 1 + ahead);
+        self.sel_char = sel.min(self.result_count().saturating_sub(1));
+        if self.sel_char < self.res_window {
+            self.res_window = self.sel_char;
+        } else if self.sel_char >= self.res_window + VISIBLE_ITEMS {
+            self.res_window = self.sel_char + 1 - VISIBLE_ITEMS;
+        }
+    }
+
+    /// Work out the detail pane for the selected character if it changed
+    fn update_detail(&mut self) {
+        let info = match self.last_query.as_ref().and_then(|lq| lq.get(self.sel_char)) {
+            Some(info) => info.clone(),
+            None => { self.detail = None; return; }
+        };
+        if self.detail.as_ref().map(|&(cp, _)| cp) != Some(info.cp) {
+            self.detail = Detail::new(&self.search, &info, self.confusables.as_ref()).ok().map(|d| (info.cp, d));
+        }
+    }
+
+    /// The selected character drawn large, then its properties and related characters, from the top of `r` down
+    unsafe fn paint_detail(&self, mut r: D2D1_RECT_F) {
+        let (info, detail) = match (self.last_query.as_ref().and_then(|lq| lq.get(self.sel_char)), self.detail.as_ref()) {
+            (Some(info), Some(&(_, ref detail))) => (info, detail),
+            _ => return
+        };
+        let glyph16 = describe::display_cluster(&[info.clone()]).encode_utf16().collect::<Vec<u16>>();
+        let gr = D2D1_RECT_F{left: r.left, right: r.right, top: r.top, bottom: r.top + 80.0};
+        self.rt.DrawText(glyph16.as_ptr(), glyph16.len() as u32, self.big_fnt.p, &gr, self.b.p,
+                         D2D1_DRAW_TEXT_OPTIONS_ENABLE_COLOR_FONT | D2D1_DRAW_TEXT_OPTIONS_CLIP, winapi::um::dcommon::DWRITE_MEASURING_MODE_NATURAL);
+        r.top += 84.0; r.bottom = r.top + 20.0;
+        let lines = detail.fields.iter().map(|(label, value)| format!("{:<16}{}", label, value))
+            .chain(detail.related.iter().map(|rc| format!("{:<16}{} U+{:04X} {}", "related",
+                                                          describe::display_cluster(&[rc.clone()]), rc.cp, rc.name)));
+        for line in lines {
+            if r.bottom > 512.0 { break; }
+            let line16 = line.encode_utf16().collect::<Vec<u16>>();
+            self.rt.DrawText(line16.as_ptr(), line16.len() as u32, self.fnt.p, &r, self.b.p,
+                             D2D1_DRAW_TEXT_OPTIONS_ENABLE_COLOR_FONT | D2D1_DRAW_TEXT_OPTIONS_CLIP, winapi::um::dcommon::DWRITE_MEASURING_MODE_NATURAL);
+            r.top += 20.0; r.bottom += 20.0;
+        }
+    }
+
     fn resize(&mut self, w: u32, h: u32) {
         self.rt.resize(w, h);
     }
 
+    /// The text after the `=` prefix, converted to the chosen form
+    fn converted_text(&self, text: &str) -> Result<String, Box<dyn Error>> {
+        match self.form {
+            Some(form) => Normalizer::new(&self.search).convert(text, form),
+            None => Ok(text.to_owned())
+        }
+    }
+
+    /// How many rows there are to move the selection through
+    fn result_count(&self) -> usize {
+        if let Some(ref rows) = self.emoji_rows { return rows.len(); }
+        match (&self.styled, &self.browse_rows, &self.last_query) {
+            (Some(rows), _, _) => rows.len(),
+            (None, Some(rows), _) => rows.len(),
+            (None, None, Some(q)) if q.is_empty() => self.suggestions.len(),
+            (None, None, Some(q)) => q.len(),
+            (None, None, None) => 0
+        }
+    }
+
+    /// `~text` shows the text in every style instead of searching for it
+    fn update_styled(&mut self, text: &str) {
+        if self.styles.is_none() {
+            match Styles::open(&self.search) {
+                Ok(st) => self.styles = Some(st),
+                Err(_) => return
+            }
+        }
+        self.styled = self.styles.as_ref().map(|st| st.all(text));
+        self.last_query = None;
+        self.sel_char = 0; self.res_window = 0;
+    }
+
+    /// Start composing on the selected character, or add the selected mark to what is being composed
+    fn add_to_composer(&mut self) {
+        let selected = match self.last_query.as_ref().and_then(|lq| lq.get(self.sel_char)) {
+            Some(info) => info.clone(),
+            None => return
+        };
+        let c = match selected.chr() { Some(c) => c, None => return };
+        match self.composer {
+            Some(ref mut composer) if compose::is_mark(&selected) => composer.push(c),
+            Some(_) => return,
+            None if !compose::is_mark(&selected) => self.composer = Some(Composer::new(c)),
+            None => return
+        }
+        self.query.clear();
+        self.update_query();
+    }
+
+    fn update_preview(&mut self) {
+        let composer = match self.composer { Some(ref c) => c, None => return };
+        let mut normalizer = Normalizer::new(&self.search);
+        self.preview = match (composer.precomposed(&mut normalizer), composer.composed(&mut normalizer)) {
+            (Ok(Some(c)), _) => format!("{} = U+{:04X}", c, c as u32),
+            (Ok(None), Ok(composed)) => format!("{}  no precomposed form", composed),
+            _ => composer.text()
+        };
+    }
+
+    /// Start or stop browsing, which starts from the list of every block, script and category
+    fn toggle_browse(&mut self) {
+        self.browser = match self.browser {
+            Some(_) => None,
+            None => browse::groups(&self.search).ok().map(Browser::new)
+        };
+        self.query.clear();
+        self.last_query = None;
+        self.update_query();
+    }
+
+    /// Start or stop picking emoji, which starts from the first tab
+    fn toggle_emoji(&mut self) {
+        if self.emoji_tab.is_some() {
+            self.emoji_tab = None;
+        } else if self.emojis.is_none() {
+            // it may have to be downloaded, so only the first time it is needed, and not on this thread
+            if self.emoji_loading.is_none() {
+                let (tx, rx) = channel();
+                let win = self.win.hndl as usize;
+                thread::spawn(move || {
+                    let _ = tx.send(Emojis::open().map_err(|e| e.to_string()));
+                    unsafe { PostMessageW(win as HWND, WM_EMOJI_LOADED, 0, 0); }
+                });
+                self.emoji_loading = Some(rx);
+            }
+            return;
+        } else if self.emojis.as_ref().is_some_and(|e| !e.groups.is_empty()) {
+            self.emoji_tab = Some(0);
+        }
+        self.query.clear();
+        self.update_query();
+    }
+
+    /// Open the first emoji tab once the thread `toggle_emoji` started has them, or say why it couldn't
+    fn emoji_loaded(&mut self) {
+        match self.emoji_loading.take().and_then(|rx| rx.recv().ok()) {
+            Some(Ok(emojis)) => {
+                self.emojis = Some(emojis);
+                self.toggle_emoji();
+            },
+            Some(Err(e)) => self.load_error = Some(format!("couldn't load the emoji list: {}", e)),
+            None => {}
+        }
+    }
+
+    /// Move to the next or previous emoji tab, wrapping around, keeping the query
+    fn switch_emoji_tab(&mut self, forward: bool) {
+        let count = self.emojis.as_ref().map_or(0, |e| e.groups.len());
+        if let Some(ref mut tab) = self.emoji_tab {
+            *tab = if forward { (*tab + 1) % count } else { (*tab + count - 1) % count };
+        }
+        self.update_query();
+    }
+
+    /// Open the selected group, listing its characters
+    fn open_group(&mut self) {
+        let selected = self.browse_rows.as_ref().and_then(|rows| rows.get(self.sel_char)).cloned();
+        if let (Some(browser), Some(g)) = (self.browser.as_mut(), selected) {
+            browser.open = Some(g);
+        }
+        self.query.clear();
+        self.update_query();
+    }
+
     fn update_query(&mut self) {
-        let q = match self.qpar.parse_query(self.query_string.as_str().trim()) {
-            Ok(v) => v,
-            Err(_) => { return; }
+        // corrections for what was typed before would be for the wrong query
+        unsafe { KillTimer(self.win.hndl, SUGGEST_TIMER); }
+        self.total_hits = None;
+        self.completion = None;
+        self.suggestions.clear();
+        self.parse_error = None;
+        self.load_error = None;
+        if self.composer.is_some() {
+            self.update_preview();
+            let results = self.search.query(&compose::marks_query(self.query.text()), 40);
+            self.show_results(results);
+            return;
+        }
+        if let (Some(emojis), Some(tab)) = (&self.emojis, self.emoji_tab) {
+            // the query only looks in the open tab
+            self.emoji_rows = Some(emojis.filter(Some(tab), self.query.text()));
+            self.last_query = None;
+            self.sel_char = 0; self.res_window = 0;
+            return;
+        }
+        self.emoji_rows = None;
+        if let Some(ref browser) = self.browser {
+            // the query narrows down the groups, or the characters of the open one by name
+            match browser.open {
+                None => {
+                    self.browse_rows = Some(browser.filter(self.query.text()));
+                    self.last_query = None;
+                },
+                Some(_) => {
+                    self.browse_rows = None;
+                    let chars = browser.chars(&self.search, self.query.text());
+                    self.show_results(chars);
+                    return;
+                }
+            }
+            self.sel_char = 0; self.res_window = 0;
+            return;
+        }
+        self.browse_rows = None;
+        if let Some(text) = self.query.text().strip_prefix(STYLE_PREFIX).map(str::to_owned) {
+            self.update_styled(&text);
+            return;
+        }
+        self.styled = None;
+        let results = match self.query.text().strip_prefix(DESCRIBE_PREFIX).map(str::to_owned) {
+            // `=text` lists the codepoints of the text instead of searching for it
+            Some(text) => match self.converted_text(&text) {
+                Ok(converted) => {
+                    self.added = diff(&text, &converted).iter()
+                        .filter(|&&(change, _)| change != Change::Removed)
+                        .map(|&(change, _)| change == Change::Added).collect();
+                    describe::char_infos(&self.search, &converted)
+                },
+                Err(e) => Err(e)
+            },
+            None => answer::answer(&self.search, self.query.text(), self.result_limit(), self.query.at_end()).map(|found| {
+                if found.total == 0 && !self.query.text().trim().is_empty() {
+                    // looking for corrections on every keystroke would slow down typing
+                    unsafe { SetTimer(self.win.hndl, SUGGEST_TIMER, SUGGEST_DELAY, None); }
+                }
+                self.total_hits = Some(found.total);
+                self.completion = found.completion;
+                self.parse_error = found.parse_error;
+                found.chars
+            })
         };
-        let s = self.index_reader.searcher(); let mut tpc = TopDocs::with_limit(40);
-        let results = s.search(&*q, &mut tpc).expect("searching index");
-        self.last_query = Some(results.iter().map(|(_, da)| s.doc(*da).unwrap()).collect());
+        self.show_results(results);
+    }
+
+    /// Show the results found for the query from the top, or why there are none
+    fn show_results(&mut self, results: Result<Vec<CharInfo>, Box<dyn Error>>) {
+        // results for what was typed before would look like results for this
+        let results = results.unwrap_or_else(|e| {
+            self.load_error = Some(format!("couldn't find the results: {}", e));
+            Vec::new()
+        });
+        self.last_query = Some(results);
+        self.sel_char = 0; self.res_window = 0;
+    }
+
+    /// Offer corrections for the query that found nothing, once typing has stopped for a moment
+    fn suggest_corrections(&mut self) {
+        unsafe { KillTimer(self.win.hndl, SUGGEST_TIMER); }
+        self.suggestions = suggest::did_you_mean(&self.search, self.query.text(), 5).unwrap_or_default();
+    }
+
+    /// Replace the results with the characters that can be mistaken for the selected one
+    fn show_confusables(&mut self) {
+        let c = match self.last_query.as_ref().and_then(|lq| lq.get(self.sel_char)).and_then(CharInfo::chr) {
+            Some(c) => c,
+            None => return
+        };
+        if self.confusables.is_none() {
+            match Confusables::open() {
+                Ok(conf) => self.confusables = Some(conf),
+                Err(e) => {
+                    self.load_error = Some(format!("couldn't load the confusables data: {}", e));
+                    return;
+                }
+            }
+        }
+        let mut results = Vec::new();
+        for o in self.confusables.as_ref().unwrap().confusable_with(c) {
+            if let Ok(Some(info)) = self.search.lookup(o as u32) {
+                results.push(info);
+            }
+        }
+        self.last_query = Some(results);
+        self.total_hits = None;
         self.sel_char = 0; self.res_window = 0;
     }
 
@@ -336,51 +750,93 @@ println!("177");
         SetForegroundWindow(self.win.hndl);
     }
     unsafe fn char_event(&mut self, w: u16) {
-        let s = String::from_utf16(&[w]).unwrap();
-        if !s.chars().next().unwrap().is_control() {
-            self.query_string.insert_str(self.cursor, &s);
-            self.cursor+=1;
+        let c = match String::from_utf16(&[w]) {
+            Ok(s) => s.chars().next().unwrap(),
+            Err(_) => return
+        };
+        if self.query.insert(c) {
             self.update_query();
         }
     }
 
-    unsafe fn send_char(&mut self, fw: HWND, cp: char, use_clipboard: bool) {
+    unsafe fn send_text(&mut self, fw: HWND, text: &str, use_clipboard: bool) {
         if use_clipboard {
-            OpenClipboard(self.win.hndl);
-            EmptyClipboard();
-            let global_text = GlobalAlloc(0x0042, 6);
-            let tcopy: &mut [u16; 3] = transmute(GlobalLock(global_text));
-            cp.encode_utf16(tcopy);
-            GlobalUnlock(global_text);
-            SetClipboardData(CF_UNICODETEXT, global_text);
-            CloseClipboard();
+            set_clipboard_text(self.win.hndl, text);
             SetForegroundWindow(fw);
-            keybd_event(VK_CONTROL as u8, 0, 0, 0);
-            keybd_event(b'V', 0, 0, 0);
-            keybd_event(b'V', 0, KEYEVENTF_KEYUP, 0);
-            keybd_event(VK_CONTROL as u8, 0, KEYEVENTF_KEYUP, 0);
+            send_paste();
         } else {
-            let mut tbuf = [0u16, 2];
-            let chb = cp.encode_utf16(&mut tbuf);
             SetForegroundWindow(fw);
-            for c in chb {
-                PostMessageW(fw, WM_CHAR, *c as WPARAM, 1);
+            for c in text.encode_utf16() {
+                PostMessageW(fw, WM_CHAR, c as WPARAM, 1);
             }
         }
     }
 
-    unsafe fn send_selected_char(&mut self, use_clipboard: bool) -> LRESULT {
-        self.query_string = String::new(); self.cursor = 0;
-        if self.foreground_window != None && self.last_query != None {
-            let fw = self.foreground_window.unwrap();
-            let cp = self.last_query.as_ref()
-                .and_then(|lq| if lq.len() > 0 { lq[self.sel_char].get_first(self.cpnf).map(Value::u64_value) } else { None })
-                .and_then(|cp| ::std::char::from_u32(cp as u32)); 
-            if let Some(cp) = cp {
-                self.send_char(fw, cp, use_clipboard);
+    /// Take what the selection stands for out of the popup, leaving an empty query behind
+    fn pick_selected(&mut self) -> Option<String> {
+        // with a form chosen, `=text` picks the whole converted text instead of the selected codepoint
+        let converted = match (self.form, self.query.text().strip_prefix(DESCRIBE_PREFIX)) {
+            (Some(_), Some(text)) => self.converted_text(text).ok(),
+            _ => None
+        };
+        // and `~text` picks the text in the selected style
+        let converted = converted.or_else(|| self.styled.as_ref().and_then(|rows| rows.get(self.sel_char)).map(|(_, text)| text.clone()));
+        // and the composer picks what it has put together
+        let converted = converted.or_else(|| self.emoji_rows.as_ref().and_then(|rows| rows.get(self.sel_char))
+                                          .and_then(|&e| self.emojis.as_ref().map(|emojis| emojis.emoji[e].text.clone())));
+        let converted = converted.or_else(|| self.composer.as_ref().and_then(|c| c.composed(&mut Normalizer::new(&self.search)).ok()));
+        let cp = self.last_query.as_ref()
+            .and_then(|lq| lq.get(self.sel_char))
+            .and_then(CharInfo::chr);
+        self.query.clear();
+        self.form = None;
+        self.styled = None;
+        self.composer = None;
+        self.last_query = None;
+        self.total_hits = None;
+        self.sel_char = 0; self.res_window = 0;
+        match (converted, cp) {
+            (Some(text), _) => Some(text),
+            (None, Some(cp)) => {
+                let _ = self.history.record(cp);
+                Some(cp.to_string())
+            },
+            (None, None) => None
+        }
+    }
+
+    /// Tab takes the completion of the word being typed, ending it so the next one can be started,
+    /// or the selected correction when the query found nothing
+    fn accept_completion(&mut self) {
+        let text = match self.completion {
+            Some(ref rest) => format!("{}{} ", self.query.text(), rest),
+            None => match self.suggestions.get(self.sel_char) {
+                Some(suggestion) => suggestion.clone(),
+                None => return
             }
-            self.foreground_window = None;
+        };
+        if self.query.set(&text) { self.update_query(); }
+    }
+
+    /// Add the selection to the compose buffer and keep the popup open for the next search
+    fn append_selected(&mut self) {
+        if let Some(text) = self.pick_selected() {
+            self.buffer.push(&text);
         }
+        // back to the whole group when browsing
+        self.update_query();
+    }
+
+    unsafe fn send_selected_char(&mut self, use_clipboard: bool) -> LRESULT {
+        let text = self.buffer.take() + &self.pick_selected().unwrap_or_default();
+        let text = self.format.apply(&text, &self.search).unwrap_or(text);
+        if let (Some(fw), false) = (self.foreground_window, text.is_empty()) {
+            self.send_text(fw, &text, use_clipboard);
+        }
+        self.format = self.default_format;
+        self.browser = None;
+        self.emoji_tab = None;
+        self.foreground_window = None;
         self.ctrl_pressed = false;
         ShowWindow(self.win.hndl, SW_HIDE);
         0
@@ -389,58 +845,126 @@ println!("177");
     unsafe fn keydown(&mut self, w: WPARAM) -> LRESULT {
         match w as i32 {
             VK_BACK => {
-                if self.cursor == 0 { 0 } else {
-                    self.cursor-=1;
-                    if self.cursor == self.query_string.len()  { self.query_string.pop(); }
-                    else { self.query_string.remove(self.cursor); }
-                    self.update_query(); 0
+                if self.query.backspace() { self.update_query(); }
+                else if let Some(ref mut composer) = self.composer {
+                    // with nothing left to erase in the query, take back the last mark, then stop composing
+                    if composer.marks.pop().is_none() { self.composer = None; }
+                    self.update_query();
+                }
+                // or go back from the open group to the list of them
+                else if self.browser.as_ref().is_some_and(|b| b.open.is_some()) {
+                    if let Some(ref mut browser) = self.browser { browser.open = None; }
+                    self.update_query();
                 }
+                // or the last pick in the compose buffer
+                else { self.buffer.pop(); }
+                0
             },
             VK_DELETE => {
-                if self.cursor == 0 { self.query_string.clear(); 0 } else {
-                    if self.cursor == self.query_string.len()  { self.cursor-=1; self.query_string.pop(); }
-                    else { self.query_string.remove(self.cursor); }
-                    self.update_query(); 0
-                }
+                if self.query.delete() { self.update_query(); }
+                0
             },
             VK_ESCAPE => { 
-                self.query_string = String::new();
+                self.query.clear();
+                self.form = None;
+                self.styled = None;
+                self.composer = None;
+                self.buffer.take();
+                self.format = self.default_format;
+                self.browser = None;
+                self.emoji_tab = None;
                 self.sel_char = 0;
                 self.res_window = 0;
-                self.cursor = 0;
                 ShowWindow(self.win.hndl, SW_HIDE); 0 
             },
             VK_CONTROL => {self.ctrl_pressed = true; 0},
+            VK_RETURN if self.browse_rows.is_some() => { self.open_group(); 0 },
+            VK_RETURN if self.last_query.as_ref().is_some_and(Vec::is_empty) && !self.suggestions.is_empty() => {
+                self.completion = None;
+                self.accept_completion();
+                0
+            },
+            VK_TAB => { self.accept_completion(); 0 },
+            VK_RETURN if GetKeyState(VK_SHIFT) < 0 => { self.append_selected(); 0 },
+            VK_RETURN if self.composer.is_some() => { self.add_to_composer(); 0 },
             VK_RETURN => { let ctlp = self.ctrl_pressed; self.send_selected_char(!ctlp) },
-            VK_UP => {
-                if self.sel_char > 0 { self.sel_char -= 1; }
-                if self.res_window > 0 && self.sel_char < self.res_window {
-                    self.res_window -= 2;
-                }
+            VK_LEFT | VK_RIGHT if self.ctrl_pressed && self.emoji_tab.is_some() => { self.switch_emoji_tab(w as i32 == VK_RIGHT); 0 },
+            VK_LEFT | VK_RIGHT | VK_UP | VK_DOWN | VK_PRIOR | VK_NEXT if self.grid_active() => {
+                let mv = match w as i32 {
+                    VK_LEFT => Move::Left,
+                    VK_RIGHT => Move::Right,
+                    VK_UP => Move::Up,
+                    VK_DOWN => Move::Down,
+                    VK_PRIOR => Move::PageUp,
+                    _ => Move::PageDown
+                };
+                let sel = self.grid.step(self.sel_char, self.available(), mv);
+                self.select(sel);
+                0
+            },
+            VK_UP => { self.select(self.sel_char.saturating_sub(1)); 0 },
+            VK_DOWN => { self.select(self.sel_char + 1); 0 },
+            VK_PRIOR => { self.select(self.sel_char.saturating_sub(VISIBLE_ITEMS)); 0 },
+            VK_NEXT => { self.select(self.sel_char + VISIBLE_ITEMS); 0 },
+            VK_HOME => { self.select(0); 0 },
+            VK_END => { self.select(self.available().saturating_sub(1)); 0 },
+            VK_LEFT => { self.query.left(); 0 },
+            VK_RIGHT => { self.query.right(); 0 },
+            0x4B /* K */ if self.ctrl_pressed => {
+                if self.composer.is_some() { self.send_selected_char(true) } else { self.add_to_composer(); 0 }
+            },
+            0x4C /* L */ if self.ctrl_pressed => { self.show_confusables(); 0 },
+            0x42 /* B */ if self.ctrl_pressed => { self.toggle_browse(); 0 },
+            0x45 /* E */ if self.ctrl_pressed => { self.toggle_emoji(); 0 },
+            0x47 /* G */ if self.ctrl_pressed => {
+                self.grid_view = !self.grid_view;
+                // the grid shows more results than the list
+                self.update_query();
+                0
+            },
+            0x55 /* U */ if self.ctrl_pressed && self.browser.is_some() => {
+                if let Some(ref mut browser) = self.browser { browser.unassigned = !browser.unassigned; }
+                self.update_query();
                 0
             },
-            VK_DOWN => {
-                match self.last_query.as_ref() {
-                    Some(q) => {
-                        if self.sel_char < q.len()-1 {
-                            if self.sel_char < self.res_window+VISIBLE_ITEMS-1 { self.sel_char += 1; }
-                            else if self.res_window < q.len() {
-                                self.res_window += 2;
-                            }
-                        }
-                    },
-                    None => {}
+            0x49 /* I */ if self.ctrl_pressed => {
+                self.show_detail = !self.show_detail;
+                // confusables are part of the related characters, when they can be had
+                if self.show_detail && self.confusables.is_none() {
+                    match Confusables::open() {
+                        Ok(conf) => self.confusables = Some(conf),
+                        Err(e) => self.load_error = Some(format!("couldn't load the confusables data: {}", e))
+                    }
+                    self.detail = None;
                 }
                 0
             },
-            VK_LEFT => { if self.cursor > 0 { self.cursor -= 1; } 0 },
-            VK_RIGHT => { if self.cursor < self.query_string.len() { self.cursor += 1; } 0 },
+            0x4F /* O */ if self.ctrl_pressed => {
+                let i = FORMATS.iter().position(|&f| f == self.format).unwrap_or(0);
+                self.format = FORMATS[(i + 1) % FORMATS.len()];
+                0
+            },
+            0x4E /* N */ if self.ctrl_pressed => {
+                // cycle through the normalization forms and case mappings, then back to the text as it is
+                self.form = match self.form {
+                    None => Some(FORMS[0]),
+                    Some(f) => FORMS.iter().position(|&g| g == f).and_then(|i| FORMS.get(i + 1)).cloned()
+                };
+                self.update_query();
+                0
+            },
             VK_PAUSE => { PostQuitMessage(0); 0 }
             _ => 1
         }
     }
 }
 
+/// Posted to the popup by the thread loading the emoji once they are ready
+const WM_EMOJI_LOADED: UINT = WM_APP + 1;
+/// The timer that looks for corrections to a query that found nothing, and how long after the last key it goes off
+const SUGGEST_TIMER: usize = 1;
+const SUGGEST_DELAY: UINT = 300;
+
 unsafe extern "system" fn winproc(win: HWND, msg: UINT, w: WPARAM, l: LPARAM) -> LRESULT {
     let papp = GetWindowLongPtrW(win, 0);
     if papp == 0 { return DefWindowProcW(win, msg, w, l); }
@@ -455,6 +979,14 @@ unsafe extern "system" fn winproc(win: HWND, msg: UINT, w: WPARAM, l: LPARAM) ->
         WM_HOTKEY => {
            app.hotkey(); 0
         },
+        WM_TIMER if w == SUGGEST_TIMER => {
+            app.suggest_corrections();
+            InvalidateRect(win, null_mut(), FALSE); 0
+        },
+        WM_EMOJI_LOADED => {
+            app.emoji_loaded();
+            InvalidateRect(win, null_mut(), FALSE); 0
+        },
         WM_CHAR => {
             app.char_event(w as u16); 1
         },
@@ -0,0 +1,220 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::io::{self, Write};
+
+use toml::Value as TomlValue;
+
+use search::{Search, CharInfo, Block};
+use describe::unlisted;
+use dmenu::{format_line, loose_eq};
+
+/// The general categories with their long names
+pub const CATEGORIES: [(&str, &str); 30] = [
+    ("Lu", "Uppercase_Letter"), ("Ll", "Lowercase_Letter"), ("Lt", "Titlecase_Letter"), ("Lm", "Modifier_Letter"),
+    ("Lo", "Other_Letter"), ("Mn", "Nonspacing_Mark"), ("Mc", "Spacing_Mark"), ("Me", "Enclosing_Mark"),
+    ("Nd", "Decimal_Number"), ("Nl", "Letter_Number"), ("No", "Other_Number"), ("Pc", "Connector_Punctuation"),
+    ("Pd", "Dash_Punctuation"), ("Ps", "Open_Punctuation"), ("Pe", "Close_Punctuation"), ("Pi", "Initial_Punctuation"),
+    ("Pf", "Final_Punctuation"), ("Po", "Other_Punctuation"), ("Sm", "Math_Symbol"), ("Sc", "Currency_Symbol"),
+    ("Sk", "Modifier_Symbol"), ("So", "Other_Symbol"), ("Zs", "Space_Separator"), ("Zl", "Line_Separator"),
+    ("Zp", "Paragraph_Separator"), ("Cc", "Control"), ("Cf", "Format"), ("Cs", "Surrogate"), ("Co", "Private_Use"),
+    ("Cn", "Unassigned")
+];
+
+/// Something to list every character of
+#[derive(Debug, Clone, PartialEq)]
+pub enum Group {
+    Block(Block),
+    /// a script as an ISO 15924 code, with how many characters it has
+    Script(String, usize),
+    /// a general category and its long name
+    Category(&'static str, &'static str)
+}
+
+impl Group {
+    pub fn label(&self) -> String {
+        match *self {
+            Group::Block(ref b) => format!("block     {} (U+{:04X}..U+{:04X})", b.name, b.first, b.last),
+            Group::Script(ref sc, count) => format!("script    {} ({} characters)", sc, count),
+            Group::Category(gc, name) => format!("category  {} {}", gc, name)
+        }
+    }
+
+    /// Whether every word of `filter` is in the label, loosely matched
+    pub fn matches(&self, filter: &str) -> bool {
+        let label = self.label();
+        filter.split_whitespace().all(|w| label.split(|c: char| c.is_whitespace() || c == '(')
+                                      .any(|l| loose_eq(w, l) || l.to_lowercase().contains(&w.to_lowercase())))
+    }
+
+    /// The characters in codepoint order. Blocks include characters that are only in the UCD as part of a range,
+    /// and with `unassigned` the codepoints that have no character at all.
+    pub fn chars(&self, search: &Search, unassigned: bool) -> Result<Vec<CharInfo>, Box<dyn Error>> {
+        match *self {
+            Group::Block(ref b) => {
+                let mut listed = search.range(b.first, b.last)?.into_iter().peekable();
+                let mut chars = Vec::new();
+                for cp in b.first..=b.last {
+                    if listed.peek().map(|c| c.cp) == Some(cp) {
+                        chars.extend(listed.next());
+                        continue;
+                    }
+                    let mut info = unlisted(search, cp);
+                    info.block = b.name.clone();
+                    if info.name.is_empty() && unassigned {
+                        info.name = format!("<reserved-{:04X}>", cp);
+                    }
+                    if !info.name.is_empty() { chars.push(info); }
+                }
+                Ok(chars)
+            },
+            Group::Script(ref sc, _) => search.script(sc),
+            Group::Category(gc, _) => search.category(gc)
+        }
+    }
+}
+
+/// Everything there is to browse: the blocks, then the scripts, then the general categories
+pub fn groups(search: &Search) -> Result<Vec<Group>, Box<dyn Error>> {
+    let mut groups = search.blocks().iter().cloned().map(Group::Block).collect::<Vec<_>>();
+    let mut scripts = BTreeMap::new();
+    for c in search.all_chars()? {
+        *scripts.entry(c.script).or_insert(0) += 1;
+    }
+    groups.extend(scripts.into_iter().map(|(sc, count)| Group::Script(sc, count)));
+    groups.extend(CATEGORIES.iter().map(|&(gc, name)| Group::Category(gc, name)));
+    Ok(groups)
+}
+
+/// Where the popup is in browsing: the list of groups, or inside one of them
+pub struct Browser {
+    pub groups: Vec<Group>,
+    pub open: Option<usize>,
+    /// whether blocks show their unassigned codepoints too
+    pub unassigned: bool
+}
+
+impl Browser {
+    pub fn new(groups: Vec<Group>) -> Browser {
+        Browser { groups, open: None, unassigned: false }
+    }
+
+    /// The indices of the groups that match the query
+    pub fn filter(&self, query: &str) -> Vec<usize> {
+        self.groups.iter().enumerate().filter(|(_, g)| g.matches(query)).map(|(i, _)| i).collect()
+    }
+
+    /// The characters of the open group, with a word of their name starting with every word of the query
+    pub fn chars(&self, search: &Search, query: &str) -> Result<Vec<CharInfo>, Box<dyn Error>> {
+        let group = match self.open { Some(i) => &self.groups[i], None => return Ok(Vec::new()) };
+        let words = query.split_whitespace().map(str::to_uppercase).collect::<Vec<_>>();
+        Ok(group.chars(search, self.unassigned)?.into_iter()
+           .filter(|c| words.iter().all(|w| c.name.split([' ', '-']).any(|n| n.starts_with(w.as_str()))))
+           .collect())
+    }
+
+    /// Open the group of a kind (`block`, `script` or `category`) with a name, returning false if there is none
+    pub fn open_named(&mut self, kind: &str, name: &str) -> bool {
+        self.open = self.groups.iter().position(|g| match (kind, g) {
+            ("block", Group::Block(b)) => loose_eq(&b.name, name),
+            ("script", Group::Script(sc, _)) => sc.eq_ignore_ascii_case(name),
+            ("category", &Group::Category(gc, long)) => gc == name || loose_eq(long, name),
+            _ => false
+        });
+        self.open.is_some()
+    }
+}
+
+/// `ununi browse [FILTER...]` lists the blocks, scripts and general categories, or the ones matching the filter.
+/// `ununi browse --block NAME`, `--script SC` or `--category GC` lists the characters of one instead, in the same
+/// format as `ununi dmenu`, with the filter matched against their names. `--unassigned` adds the unassigned codepoints of a block.
+pub fn run(config: &Option<TomlValue>, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let usage = "usage: ununi browse [--block NAME | --script SC | --category GC] [--unassigned] [FILTER...]";
+    let mut chosen = None;
+    let mut unassigned = false;
+    let mut words = Vec::new();
+    let mut args = args.iter();
+    while let Some(a) = args.next() {
+        match a.as_str() {
+            "--block" | "--script" | "--category" => chosen = Some((a[2..].to_owned(), args.next().ok_or(usage)?.clone())),
+            "--unassigned" => unassigned = true,
+            _ if a.starts_with("--") => return Err(usage.into()),
+            _ => words.push(a.as_str())
+        }
+    }
+    let filter = words.join(" ");
+    let search = Search::open(config)?;
+    let mut browser = Browser::new(groups(&search)?);
+    browser.unassigned = unassigned;
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let (kind, name) = match chosen {
+        Some(chosen) => chosen,
+        None => {
+            for i in browser.filter(&filter) {
+                writeln!(out, "{}", browser.groups[i].label())?;
+            }
+            return Ok(());
+        }
+    };
+    if !browser.open_named(&kind, &name) {
+        return Err(format!("no {} named {}", kind, name).into());
+    }
+    for c in browser.chars(&search, &filter)? {
+        if let Err(e) = writeln!(out, "{}", format_line(&c)) {
+            if e.kind() == io::ErrorKind::BrokenPipe { break; }
+            return Err(Box::new(e));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ::browse::*;
+    use ::search::tests::fixture;
+
+    const CHARS: &str = r#"<group blk="Latin_1_Sup" gc="Ll" sc="Latn" bc="L">
+<char cp="00DF" na="LATIN SMALL LETTER SHARP S"/>
+<char cp="00E9" na="LATIN SMALL LETTER E WITH ACUTE"/>
+</group>
+<group blk="Cyrillic" gc="Ll" sc="Cyrl" bc="L">
+<char cp="0430" na="CYRILLIC SMALL LETTER A"/>
+</group>
+<group blk="Tangut" gc="Lo" sc="Tang" bc="L">
+<char first-cp="17000" last-cp="187F7" na="TANGUT IDEOGRAPH-#"/>
+</group>
+"#;
+
+    #[test]
+    fn browses() {
+        let s = fixture(CHARS);
+        let b = Browser::new(groups(&s).unwrap());
+        let labels = b.filter("arrows").iter().map(|&i| b.groups[i].label()).collect::<Vec<_>>();
+        assert_eq!(labels, vec!["block     Arrows (U+2190..U+21FF)"]);
+        assert_eq!(b.filter("Cyrl").len(), 1);
+        assert_eq!(b.filter("math symbol").iter().map(|&i| b.groups[i].clone()).collect::<Vec<_>>(), vec![Group::Category("Sm", "Math_Symbol")]);
+
+        let arrows = Group::Block(s.blocks()[2].clone());
+        assert_eq!(arrows.chars(&s, false).unwrap().iter().map(|c| c.cp).collect::<Vec<_>>(), vec![0x2190, 0x2192]);
+        let all = arrows.chars(&s, true).unwrap();
+        assert_eq!(all.len(), 0x70);
+        assert_eq!(all[1].name, "<reserved-2191>");
+        // Hangul syllables are only in the UCD as a range
+        let hangul = Block { first: 0xac00, last: 0xd7af, name: "Hangul Syllables".into() };
+        assert_eq!(Group::Block(hangul).chars(&s, false).unwrap().len(), 11172);
+        let tangut = Block { first: 0x17000, last: 0x187ff, name: "Tangut".into() };
+        let tangut = Group::Block(tangut).chars(&s, false).unwrap();
+        assert_eq!((tangut.len(), tangut[1].name.as_str()), (0x17f8, "TANGUT IDEOGRAPH-17001"));
+    }
+
+    #[test]
+    fn filters_open_group() {
+        let s = fixture(CHARS);
+        let mut b = Browser::new(groups(&s).unwrap());
+        assert!(b.open_named("block", "latin 1 supplement"));
+        assert_eq!(b.chars(&s, "letter e").unwrap().iter().map(|c| c.cp).collect::<Vec<_>>(), vec![0xe9]);
+        assert!(b.open_named("category", "Math_Symbol"));
+        assert_eq!(b.chars(&s, "").unwrap().len(), 2);
+        assert!(!b.open_named("script", "Zzzz"));
+    }
+}
@@ -0,0 +1,126 @@
+use std::error::Error;
+
+use toml::Value as TomlValue;
+
+use search::{Search, CharInfo};
+use normalize::{Normalizer, Form};
+
+/// Nonspacing and enclosing marks, the ones that are drawn on top of the character before them
+pub fn is_mark(c: &CharInfo) -> bool {
+    c.category == "Mn" || c.category == "Me"
+}
+
+/// Restrict a query to the marks that can be added to a base character
+pub fn marks_query(query: &str) -> String {
+    if query.trim().is_empty() {
+        String::from("gc:Mn OR gc:Me")
+    } else {
+        format!("({}) AND (gc:Mn OR gc:Me)", query)
+    }
+}
+
+/// A base character with the combining marks added to it so far
+pub struct Composer {
+    base: char,
+    pub marks: Vec<char>
+}
+
+impl Composer {
+    pub fn new(base: char) -> Composer {
+        Composer { base, marks: Vec::new() }
+    }
+
+    pub fn push(&mut self, mark: char) {
+        self.marks.push(mark);
+    }
+
+    /// The base followed by the marks in the order they were added
+    pub fn text(&self) -> String {
+        Some(self.base).into_iter().chain(self.marks.iter().cloned()).collect()
+    }
+
+    /// The composed text, which is a single precomposed character when Unicode has one
+    pub fn composed(&self, normalizer: &mut Normalizer) -> Result<String, Box<dyn Error>> {
+        normalizer.convert(&self.text(), Form::Nfc)
+    }
+
+    pub fn precomposed(&self, normalizer: &mut Normalizer) -> Result<Option<char>, Box<dyn Error>> {
+        let composed = self.composed(normalizer)?;
+        let mut chars = composed.chars();
+        Ok(match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(c),
+            _ => None
+        })
+    }
+}
+
+/// `ununi compose BASE [MARK...]` adds each mark to BASE and prints the result, with the codepoint of the
+/// precomposed character when there is one. A MARK is either the mark itself or a query for its name.
+pub fn run(config: &Option<TomlValue>, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut base = args.first().map_or("", String::as_str).chars();
+    let base = match (base.next(), base.next()) {
+        (Some(b), None) => b,
+        _ => return Err("usage: ununi compose BASE [MARK...]".into())
+    };
+    let search = Search::open(config)?;
+    let mut composer = Composer::new(base);
+    for q in &args[1..] {
+        let mut chars = q.chars();
+        let given = match (chars.next(), chars.next()) {
+            (Some(c), None) => search.lookup(c as u32)?.filter(is_mark),
+            _ => None
+        };
+        let mark = match given {
+            Some(info) => info.chr(),
+            None => search.query(&marks_query(q), 1)?.first().and_then(CharInfo::chr)
+        }.ok_or_else(|| format!("no combining mark matches {}", q))?;
+        composer.push(mark);
+    }
+    let mut normalizer = Normalizer::new(&search);
+    match composer.precomposed(&mut normalizer)? {
+        Some(c) => println!("{}\tU+{:04X}", c, c as u32),
+        None => println!("{}\tno precomposed form", composer.composed(&mut normalizer)?)
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ::compose::*;
+    use ::search::tests::fixture;
+
+    const CHARS: &str = r#"<group blk="ASCII" gc="Ll" sc="Latn" bc="L">
+<char cp="0065" na="LATIN SMALL LETTER E"/>
+</group>
+<group blk="Latin_1_Sup" gc="Ll" sc="Latn" bc="L">
+<char cp="00E9" na="LATIN SMALL LETTER E WITH ACUTE" dt="can" dm="0065 0301"/>
+</group>
+<group blk="Diacriticals" gc="Mn" sc="Zinh" bc="NSM">
+<char cp="0301" na="COMBINING ACUTE ACCENT" ccc="230"/>
+<char cp="0323" na="COMBINING DOT BELOW" ccc="220"/>
+</group>
+"#;
+
+    #[test]
+    fn finds_marks() {
+        let s = fixture(CHARS);
+        let found = s.query(&marks_query("acute"), 10).unwrap().iter().map(|c| c.cp).collect::<Vec<_>>();
+        assert!(found.contains(&0x301));
+        assert!(!found.contains(&0xe9));
+        assert!(s.query(&marks_query(""), 10).unwrap().iter().all(is_mark));
+    }
+
+    #[test]
+    fn composes() {
+        let s = fixture(CHARS);
+        let mut n = Normalizer::new(&s);
+        let mut c = Composer::new('e');
+        c.push('\u{301}');
+        assert_eq!(c.precomposed(&mut n).unwrap(), Some('\u{e9}'));
+        c.push('\u{323}');
+        // marks are put in canonical order, and there is no e with acute and dot below
+        assert_eq!(c.precomposed(&mut n).unwrap(), None);
+        assert_eq!(c.composed(&mut n).unwrap(), "\u{e9}\u{323}");
+        assert_eq!(c.text(), "e\u{301}\u{323}");
+    }
+}
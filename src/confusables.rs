@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::{self, BufRead, BufReader, Write};
+
+use toml::Value as TomlValue;
+
+use search::{Search, data_file};
+use dmenu::format_line;
+use normalize::{Normalizer, Form};
+
+const CONFUSABLES_URL: &str = "https://www.unicode.org/Public/security/latest/confusables.txt";
+const INTENTIONAL_URL: &str = "https://www.unicode.org/Public/security/latest/intentional.txt";
+
+/// Parse a string of space separated hex codepoints like `0072 006E`
+fn parse_codepoints(s: &str) -> Option<String> {
+    s.split_whitespace().map(|hex| u32::from_str_radix(hex, 16).ok().and_then(::std::char::from_u32)).collect()
+}
+
+/// Read the `source ; target ; ...` lines of one of the UTS #39 data files
+fn parse_mappings<R: BufRead>(r: R) -> Result<Vec<(char, String)>, Box<dyn Error>> {
+    let mut mappings = Vec::new();
+    for line in r.lines() {
+        let line = line?;
+        let line = line.trim_start_matches('\u{feff}');
+        let data = line.split('#').next().unwrap_or("");
+        let mut fields = data.split(';');
+        let (source, target) = match (fields.next(), fields.next()) {
+            (Some(source), Some(target)) => (source, target),
+            _ => continue
+        };
+        let source = parse_codepoints(source).ok_or("bad codepoint in confusables data")?;
+        let target = parse_codepoints(target).ok_or("bad codepoint in confusables data")?;
+        let mut source = source.chars();
+        if let (Some(source), None) = (source.next(), source.next()) {
+            mappings.push((source, target));
+        }
+    }
+    Ok(mappings)
+}
+
+/// The UTS #39 confusables data: which characters are drawn like which others
+pub struct Confusables {
+    prototypes: HashMap<char, String>,
+    /// every character with a particular skeleton, to go from a character to the ones it can be confused with
+    by_skeleton: HashMap<String, Vec<char>>,
+    /// pairs that are the same on purpose, like Cyrillic and Latin capital A, in both directions
+    intentional: HashMap<char, Vec<char>>
+}
+
+impl Confusables {
+    pub fn parse<R: BufRead, S: BufRead>(confusables: R, intentional: S) -> Result<Confusables, Box<dyn Error>> {
+        let mut prototypes = HashMap::new();
+        let mut by_skeleton: HashMap<String, Vec<char>> = HashMap::new();
+        for (source, target) in parse_mappings(confusables)? {
+            let group = by_skeleton.entry(target.clone()).or_default();
+            group.push(source);
+            // prototypes are their own skeleton
+            let mut t = target.chars();
+            if let (Some(p), None) = (t.next(), t.next()) {
+                if !group.contains(&p) { group.push(p); }
+            }
+            prototypes.insert(source, target);
+        }
+        let mut intentional_pairs: HashMap<char, Vec<char>> = HashMap::new();
+        for (source, target) in parse_mappings(intentional)? {
+            if let Some(target) = target.chars().next() {
+                intentional_pairs.entry(source).or_default().push(target);
+                intentional_pairs.entry(target).or_default().push(source);
+            }
+        }
+        Ok(Confusables { prototypes, by_skeleton, intentional: intentional_pairs })
+    }
+
+    /// Load confusables.txt and intentional.txt from the current directory, downloading them if they aren't there
+    pub fn open() -> Result<Confusables, Box<dyn Error>> {
+        let confusables = data_file("./confusables.txt", CONFUSABLES_URL)?;
+        let intentional = data_file("./intentional.txt", INTENTIONAL_URL)?;
+        Confusables::parse(BufReader::new(confusables), BufReader::new(intentional))
+    }
+
+    /// Replace every character of `s` with its prototype
+    fn map_prototypes(&self, s: &str) -> String {
+        s.chars().map(|c| self.prototypes.get(&c).cloned().unwrap_or_else(|| c.to_string())).collect()
+    }
+
+    /// The UTS #39 skeleton of `s`: two strings are confusable when their skeletons are the same
+    pub fn skeleton(&self, s: &str, normalizer: &mut Normalizer) -> Result<String, Box<dyn Error>> {
+        let mapped = self.map_prototypes(&normalizer.convert(s, Form::Nfd)?);
+        normalizer.convert(&mapped, Form::Nfd)
+    }
+
+    /// Every other character that can be mistaken for `c`, in codepoint order
+    pub fn confusable_with(&self, c: char) -> Vec<char> {
+        let mut found = self.by_skeleton.get(&self.map_prototypes(&c.to_string())).cloned().unwrap_or_default();
+        found.extend(self.intentional.get(&c).into_iter().flatten());
+        found.retain(|&o| o != c);
+        found.sort();
+        found.dedup();
+        found
+    }
+}
+
+/// `ununi skeleton STRING [OTHER]` prints the skeleton of a string, or compares two of them.
+/// When comparing, the status to exit with is 0 if the strings are confusable and 1 if they aren't.
+pub fn run_skeleton(config: &Option<TomlValue>, args: &[String]) -> Result<i32, Box<dyn Error>> {
+    let (a, b) = match args {
+        [a] => (a, None),
+        [a, b] => (a, Some(b)),
+        _ => return Err("usage: ununi skeleton STRING [OTHER]".into())
+    };
+    let confusables = Confusables::open()?;
+    let search = Search::open(config)?;
+    let mut normalizer = Normalizer::new(&search);
+    let skeleton = confusables.skeleton(a, &mut normalizer)?;
+    println!("{}", skeleton);
+    if let Some(b) = b {
+        let other = confusables.skeleton(b, &mut normalizer)?;
+        println!("{}", other);
+        if skeleton == other {
+            println!("confusable");
+        } else {
+            println!("not confusable");
+            return Ok(1);
+        }
+    }
+    Ok(0)
+}
+
+/// `ununi confusables CHAR` lists the characters that can be mistaken for CHAR, in the same format as `ununi dmenu`
+pub fn run_confusables(config: &Option<TomlValue>, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut chars = args.first().map_or("", String::as_str).chars();
+    let c = match (chars.next(), chars.next(), args.len()) {
+        (Some(c), None, 1) => c,
+        _ => return Err("usage: ununi confusables CHAR".into())
+    };
+    let confusables = Confusables::open()?;
+    let search = Search::open(config)?;
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    for o in confusables.confusable_with(c) {
+        match search.lookup(o as u32)? {
+            Some(info) => writeln!(out, "{}", format_line(&info))?,
+            None => writeln!(out, "{}\tU+{:04X}", o, o as u32)?
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ::confusables::*;
+    use ::search::tests::fixture;
+
+    const CHARS: &str = r#"<group blk="Latin_1_Sup" gc="Ll" sc="Latn" bc="L">
+<char cp="00E9" na="LATIN SMALL LETTER E WITH ACUTE" dt="can" dm="0065 0301"/>
+</group>
+<group blk="Diacriticals" gc="Mn" sc="Zinh" bc="NSM">
+<char cp="0301" na="COMBINING ACUTE ACCENT" ccc="230"/>
+</group>
+"#;
+
+    const CONFUSABLES: &str = "\u{feff}# confusables.txt
+0430 ;\t0061 ;\tMA\t# ( а → a ) CYRILLIC SMALL LETTER A → LATIN SMALL LETTER A
+0251 ;\t0061 ;\tMA\t# ( ɑ → a ) LATIN SMALL LETTER ALPHA → LATIN SMALL LETTER A
+0440 ;\t0070 ;\tMA\t# ( р → p ) CYRILLIC SMALL LETTER ER → LATIN SMALL LETTER P
+006D ;\t0072 006E ;\tMA\t# ( m → rn ) LATIN SMALL LETTER M → LATIN SMALL LETTER R, LATIN SMALL LETTER N
+
+0031 ;\t006C ;\tMA\t# ( 1 → l ) DIGIT ONE → LATIN SMALL LETTER L
+";
+    const INTENTIONAL: &str = "0041 ;\t0410 #* ( A ~ А ) LATIN CAPITAL LETTER A ~ CYRILLIC CAPITAL LETTER A\n";
+
+    fn confusables() -> Confusables {
+        Confusables::parse(CONFUSABLES.as_bytes(), INTENTIONAL.as_bytes()).expect("parsing confusables")
+    }
+
+    #[test]
+    fn skeletons() {
+        let c = confusables();
+        let s = fixture(CHARS);
+        let mut n = Normalizer::new(&s);
+        let mut skeleton = |text| c.skeleton(text, &mut n).unwrap();
+        assert_eq!(skeleton("р\u{430}yp\u{251}l"), "paypal");
+        assert_eq!(skeleton("example1"), "exarnplel");
+        assert_eq!(skeleton("exarnple1"), skeleton("example1"));
+        assert_ne!(skeleton("paypal"), skeleton("paypa1x"));
+        // decomposed before and after mapping
+        assert_eq!(skeleton("caf\u{e9}"), "cafe\u{301}");
+    }
+
+    #[test]
+    fn lists_confusables() {
+        let c = confusables();
+        assert_eq!(c.confusable_with('a'), vec!['\u{251}', '\u{430}']);
+        assert_eq!(c.confusable_with('\u{430}'), vec!['a', '\u{251}']);
+        assert_eq!(c.confusable_with('A'), vec!['\u{410}']);
+        assert_eq!(c.confusable_with('\u{410}'), vec!['A']);
+        assert!(c.confusable_with('z').is_empty());
+    }
+}
@@ -0,0 +1,362 @@
+use std::convert::TryFrom;
+use std::error::Error;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use serde_json::Value as JsonValue;
+use toml::Value as TomlValue;
+
+use search::{Search, CharInfo, QError, literal_query};
+use history::History;
+use suggest;
+
+#[cfg(windows)]
+const PIPE_NAME: &str = "\\\\.\\pipe\\ununi";
+
+/// JSON for a single character, shared by everything that speaks JSON
+pub fn char_json(c: &CharInfo) -> JsonValue {
+    json!({
+        "cp": c.cp,
+        "codepoint": format!("U+{:04X}", c.cp),
+        "char": c.chr().map(|ch| ch.to_string()),
+        "name": c.name,
+        "block": c.block,
+        "category": c.category,
+        "aliases": c.aliases,
+        "script": c.script,
+        "bidi_class": c.bidi_class,
+        "age": c.age
+    })
+}
+
+#[derive(Debug)]
+pub struct RpcError {
+    code: i64,
+    message: String,
+    /// more about what went wrong, for the client to act on
+    data: Option<JsonValue>
+}
+
+impl RpcError {
+    fn invalid_params(message: &str) -> RpcError {
+        RpcError { code: -32602, message: message.to_owned(), data: None }
+    }
+}
+
+impl From<Box<dyn Error>> for RpcError {
+    fn from(e: Box<dyn Error>) -> RpcError {
+        RpcError { code: -32000, message: e.to_string(), data: None }
+    }
+}
+
+fn error_response(id: JsonValue, code: i64, message: &str, data: Option<JsonValue>) -> String {
+    let mut error = json!({ "code": code, "message": message });
+    if let Some(data) = data { error["data"] = data; }
+    json!({ "jsonrpc": "2.0", "id": id, "error": error }).to_string()
+}
+
+/// The most results a request can ask for, so no client can make the search allocate without bound
+const MAX_LIMIT: u64 = 1000;
+
+/// Read the `limit` of a request, `default` when it has none
+fn param_limit(params: &JsonValue, default: usize) -> Result<usize, RpcError> {
+    match params.get("limit") {
+        None | Some(JsonValue::Null) => Ok(default),
+        Some(v) => match v.as_u64() {
+            Some(limit) if limit <= MAX_LIMIT => Ok(limit as usize),
+            _ => Err(RpcError::invalid_params(&format!("expected a `limit` from 0 to {}", MAX_LIMIT)))
+        }
+    }
+}
+
+/// Read the character a request is about, given either as `cp` (a number) or `char` (a string)
+fn param_char(params: &JsonValue) -> Result<char, RpcError> {
+    params.get("cp").and_then(JsonValue::as_u64)
+        .and_then(|cp| u32::try_from(cp).ok()).and_then(::std::char::from_u32)
+        .or_else(|| params.get("char").and_then(JsonValue::as_str).and_then(|s| s.chars().next()))
+        .ok_or_else(|| RpcError::invalid_params("expected a codepoint in `cp` or a character in `char`"))
+}
+
+type InsertFn = dyn Fn(&str) -> Result<(), Box<dyn Error>> + Send + Sync;
+
+/// Keeps the index open and answers JSON-RPC requests from any number of clients
+pub struct Daemon {
+    search: Search,
+    history: Mutex<History>,
+    insert: Box<InsertFn>
+}
+
+impl Daemon {
+    pub fn new(search: Search, history: History, insert: Box<InsertFn>) -> Daemon {
+        Daemon { search, history: Mutex::new(history), insert }
+    }
+
+    fn call(&self, method: &str, params: &JsonValue) -> Result<JsonValue, RpcError> {
+        match method {
+            "search" => {
+                let query = params.get("query").and_then(JsonValue::as_str)
+                    .ok_or_else(|| RpcError::invalid_params("expected a query string in `query`"))?;
+                let limit = param_limit(params, 40)?;
+                let results = self.search.query(query, limit).map_err(|e| match e.downcast_ref::<QError>() {
+                    // with where the query is wrong and its words to search for instead, like the HTTP service
+                    Some(qe) => RpcError { code: -32000, message: qe.message.clone(),
+                                           data: Some(json!({ "position": qe.position, "literal": literal_query(query) })) },
+                    None => RpcError::from(e)
+                })?;
+                Ok(JsonValue::Array(results.iter().map(char_json).collect()))
+            },
+            "complete" => {
+                let query = params.get("query").and_then(JsonValue::as_str)
+                    .ok_or_else(|| RpcError::invalid_params("expected a query string in `query`"))?;
+                let limit = param_limit(params, 5)?;
+                let rest = suggest::complete(&self.search, query)?;
+                let suggestions = if self.search.query_page(query, 0, 1)?.total == 0 {
+                    suggest::did_you_mean(&self.search, query, limit)?
+                } else {
+                    Vec::new()
+                };
+                Ok(json!({ "completion": rest.map(|r| format!("{}{}", query, r)), "suggestions": suggestions }))
+            },
+            "lookup" => {
+                let c = param_char(params)?;
+                Ok(self.search.lookup(c as u32)?.as_ref().map_or(JsonValue::Null, char_json))
+            },
+            "history" => {
+                let limit = param_limit(params, MAX_LIMIT as usize)?;
+                let history = History::open(self.history.lock().unwrap().path())?;
+                let mut chars = Vec::new();
+                for c in history.entries().iter().take(limit) {
+                    if let Some(info) = self.search.lookup(*c as u32)? {
+                        chars.push(char_json(&info));
+                    }
+                }
+                Ok(JsonValue::Array(chars))
+            },
+            "insert" => {
+                let text = match params.get("text").and_then(JsonValue::as_str) {
+                    Some(text) => text.to_owned(),
+                    None => param_char(params)?.to_string()
+                };
+                (self.insert)(&text)?;
+                let mut chars = text.chars();
+                if let (Some(c), None) = (chars.next(), chars.next()) {
+                    self.history.lock().unwrap().record(c)?;
+                }
+                Ok(JsonValue::Bool(true))
+            },
+            _ => Err(RpcError { code: -32601, message: format!("unknown method {}", method), data: None })
+        }
+    }
+
+    /// Handle one JSON-RPC message, returning the response unless it was a notification
+    pub fn handle(&self, message: &str) -> Option<String> {
+        let req: JsonValue = match serde_json::from_str(message) {
+            Ok(v) => v,
+            Err(e) => return Some(error_response(JsonValue::Null, -32700, &e.to_string(), None))
+        };
+        let id = req.get("id").cloned();
+        let method = match req.get("method").and_then(JsonValue::as_str) {
+            Some(m) => m,
+            None => return Some(error_response(id.unwrap_or(JsonValue::Null), -32600, "expected a `method`", None))
+        };
+        let result = self.call(method, req.get("params").unwrap_or(&JsonValue::Null));
+        let id = id?;
+        Some(match result {
+            Ok(v) => json!({ "jsonrpc": "2.0", "id": id, "result": v }).to_string(),
+            Err(e) => error_response(id, e.code, &e.message, e.data)
+        })
+    }
+
+    /// Answer newline separated requests from one client until it hangs up
+    pub fn serve<R: Read, W: Write>(&self, input: R, mut output: W) -> io::Result<()> {
+        for line in BufReader::new(input).lines() {
+            let line = line?;
+            if line.trim().is_empty() { continue; }
+            if let Some(resp) = self.handle(&line) {
+                writeln!(output, "{}", resp)?;
+                output.flush()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn socket_path() -> ::std::path::PathBuf {
+    ::std::env::var_os("XDG_RUNTIME_DIR").map(::std::path::PathBuf::from)
+        .unwrap_or_else(|| ::std::path::PathBuf::from("."))
+        .join("ununi.sock")
+}
+
+#[cfg(unix)]
+fn listen(daemon: Arc<Daemon>, path: ::std::path::PathBuf) -> Result<(), Box<dyn Error>> {
+    use std::os::unix::net::{UnixListener, UnixStream};
+    if path.exists() {
+        if UnixStream::connect(&path).is_ok() {
+            return Err(format!("a daemon is already listening on {}", path.display()).into());
+        }
+        ::std::fs::remove_file(&path)?;
+    }
+    let listener = UnixListener::bind(&path)?;
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => { eprintln!("accepting a connection failed: {}", e); continue; }
+        };
+        let daemon = daemon.clone();
+        thread::spawn(move || {
+            if let Ok(input) = stream.try_clone() {
+                let _ = daemon.serve(input, stream);
+            }
+        });
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn listen(daemon: Arc<Daemon>, name: String) -> Result<(), Box<dyn Error>> {
+    use std::fs::File;
+    use std::os::windows::io::FromRawHandle;
+    use std::ptr::null_mut;
+    use winapi::um::namedpipeapi::{CreateNamedPipeW, ConnectNamedPipe};
+    use winapi::um::winbase::{PIPE_ACCESS_DUPLEX, PIPE_TYPE_BYTE, PIPE_READMODE_BYTE, PIPE_WAIT, PIPE_UNLIMITED_INSTANCES};
+    use winapi::um::handleapi::{CloseHandle, INVALID_HANDLE_VALUE};
+    use winapi::um::errhandlingapi::GetLastError;
+    use winapi::shared::winerror::ERROR_PIPE_CONNECTED;
+    use vgu::HResultError;
+
+    let wname = name.encode_utf16().chain(Some(0)).collect::<Vec<u16>>();
+    loop {
+        let pipe = unsafe { CreateNamedPipeW(wname.as_ptr(), PIPE_ACCESS_DUPLEX, PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT,
+                                             PIPE_UNLIMITED_INSTANCES, 4096, 4096, 0, null_mut()) };
+        if pipe == INVALID_HANDLE_VALUE {
+            return Err(Box::new(HResultError::last_win32_error()));
+        }
+        if unsafe { ConnectNamedPipe(pipe, null_mut()) } == 0 && unsafe { GetLastError() } != ERROR_PIPE_CONNECTED {
+            unsafe { CloseHandle(pipe); }
+            continue;
+        }
+        let stream = unsafe { File::from_raw_handle(pipe as _) };
+        let daemon = daemon.clone();
+        thread::spawn(move || {
+            if let Ok(input) = stream.try_clone() {
+                let _ = daemon.serve(input, stream);
+            }
+        });
+    }
+}
+
+/// `ununi daemon [--socket PATH]` keeps the index warm and serves requests until killed
+pub fn run(config: &Option<TomlValue>, args: &[String]) -> Result<(), Box<dyn Error>> {
+    #[cfg(unix)]
+    let mut address = socket_path();
+    #[cfg(windows)]
+    let mut address = String::from(PIPE_NAME);
+    let mut args = args.iter();
+    while let Some(a) = args.next() {
+        match a.as_str() {
+            "--socket" => address = args.next().ok_or("--socket needs a path")?.into(),
+            _ => return Err(format!("unknown argument {}; usage: ununi daemon [--socket PATH]", a).into())
+        }
+    }
+
+    // there is no way to get text into other windows on Linux yet
+    #[cfg(unix)]
+    let insert: Box<InsertFn> = Box::new(|_: &str| Err("inserting text is only supported on Windows".into()));
+    #[cfg(windows)]
+    let insert: Box<InsertFn> = {
+        let _ = config;
+        Box::new(|text: &str| ::app::paste_text(text))
+    };
+    let search = Search::open(config)?;
+    let daemon = Daemon::new(search, History::open("./history")?, insert);
+    listen(Arc::new(daemon), address)
+}
+
+#[cfg(test)]
+mod tests {
+    use ::daemon::*;
+    use ::search::tests::fixture;
+
+    fn test_daemon(name: &str) -> (Daemon, Arc<Mutex<Vec<String>>>) {
+        let path = ::std::env::temp_dir().join(format!("ununi-daemon-test-{}-{}", name, ::std::process::id()));
+        let _ = ::std::fs::remove_file(&path);
+        let inserted = Arc::new(Mutex::new(Vec::new()));
+        let ins = inserted.clone();
+        (Daemon::new(fixture(""), History::open(path).unwrap(),
+                     Box::new(move |text: &str| { ins.lock().unwrap().push(text.to_owned()); Ok(()) })), inserted)
+    }
+
+    fn call(d: &Daemon, req: JsonValue) -> JsonValue {
+        serde_json::from_str(&d.handle(&req.to_string()).expect("a response")).unwrap()
+    }
+
+    #[test]
+    fn search_and_lookup() {
+        let (d, _) = test_daemon("search");
+        let res = call(&d, json!({"jsonrpc": "2.0", "id": 1, "method": "search", "params": {"query": "lamda"}}));
+        assert_eq!(res["id"], 1);
+        assert_eq!(res["result"][0]["char"], "λ");
+        assert_eq!(res["result"][0]["codepoint"], "U+03BB");
+        let res = call(&d, json!({"jsonrpc": "2.0", "id": 2, "method": "lookup", "params": {"char": "→"}}));
+        assert_eq!(res["result"]["name"], "RIGHTWARDS ARROW");
+        let res = call(&d, json!({"jsonrpc": "2.0", "id": 3, "method": "lookup", "params": {"cp": 0x2191}}));
+        assert_eq!(res["result"], JsonValue::Null);
+        let res = call(&d, json!({"jsonrpc": "2.0", "id": 4, "method": "complete", "params": {"query": "leftwards arr"}}));
+        assert_eq!(res["result"], json!({"completion": "leftwards arrow", "suggestions": []}));
+        let res = call(&d, json!({"jsonrpc": "2.0", "id": 5, "method": "complete", "params": {"query": "lamda AND grek"}}));
+        assert_eq!(res["result"], json!({"completion": null, "suggestions": ["lamda AND greek"]}));
+    }
+
+    #[test]
+    fn insert_records_history() {
+        let (d, inserted) = test_daemon("insert");
+        call(&d, json!({"jsonrpc": "2.0", "id": 1, "method": "insert", "params": {"cp": 0x2192}}));
+        call(&d, json!({"jsonrpc": "2.0", "id": 2, "method": "insert", "params": {"text": "AB"}}));
+        assert_eq!(*inserted.lock().unwrap(), vec!["→", "AB"]);
+        let res = call(&d, json!({"jsonrpc": "2.0", "id": 3, "method": "history"}));
+        assert_eq!(res["result"].as_array().unwrap().len(), 1);
+        assert_eq!(res["result"][0]["cp"], 0x2192);
+        ::std::fs::remove_file(d.history.lock().unwrap().path()).unwrap();
+    }
+
+    #[test]
+    fn errors_and_notifications() {
+        let (d, _) = test_daemon("errors");
+        assert_eq!(call(&d, json!({"jsonrpc": "2.0", "id": 1, "method": "nope"}))["error"]["code"], -32601);
+        assert_eq!(call(&d, json!({"jsonrpc": "2.0", "id": 1, "method": "lookup", "params": {}}))["error"]["code"], -32602);
+        assert_eq!(call(&d, json!({"jsonrpc": "2.0", "id": 1, "method": "lookup", "params": {"cp": 0x1_0000_0041u64}}))["error"]["code"], -32602);
+        for method in &["search", "complete", "history"] {
+            let huge = json!({"jsonrpc": "2.0", "id": 1, "method": method, "params": {"query": "arrow", "limit": u64::MAX}});
+            assert_eq!(call(&d, huge)["error"]["code"], -32602);
+        }
+        let error = call(&d, json!({"jsonrpc": "2.0", "id": 1, "method": "search", "params": {"query": "name: arrow"}}))["error"].clone();
+        assert_eq!((error["code"].as_i64(), error["data"]["position"].as_u64()), (Some(-32000), Some(4)));
+        assert_eq!(error["data"]["literal"], "name arrow");
+        let res: JsonValue = serde_json::from_str(&d.handle("{").unwrap()).unwrap();
+        assert_eq!(res["error"]["code"], -32700);
+        assert_eq!(d.handle(r#"{"jsonrpc": "2.0", "method": "lookup", "params": {"cp": 65}}"#), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn serves_over_a_socket() {
+        use std::os::unix::net::UnixStream;
+        let (d, _) = test_daemon("socket");
+        let path = ::std::env::temp_dir().join(format!("ununi-test-{}.sock", ::std::process::id()));
+        let d = Arc::new(d);
+        {
+            let path = path.clone();
+            thread::spawn(move || listen(d, path).unwrap());
+        }
+        let mut stream = (0..50).filter_map(|_| UnixStream::connect(&path).map_err(|_| thread::sleep(::std::time::Duration::from_millis(20))).ok())
+            .next().expect("connecting to the daemon");
+        writeln!(stream, r#"{{"jsonrpc": "2.0", "id": 7, "method": "lookup", "params": {{"cp": 65}}}}"#).unwrap();
+        let mut line = String::new();
+        BufReader::new(stream).read_line(&mut line).unwrap();
+        let res: JsonValue = serde_json::from_str(&line).unwrap();
+        assert_eq!(res["result"]["name"], "LATIN CAPITAL LETTER A");
+        let _ = ::std::fs::remove_file(&path);
+    }
+}
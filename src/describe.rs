@@ -0,0 +1,235 @@
+use std::error::Error;
+use std::io::{self, Read, Write};
+
+use toml::Value as TomlValue;
+
+use search::{Search, CharInfo};
+
+const HANGUL_BASE: u32 = 0xAC00;
+const HANGUL_COUNT: u32 = 11172;
+const JAMO_L: [&str; 19] = ["G", "GG", "N", "D", "DD", "R", "M", "B", "BB", "S", "SS", "", "J", "JJ", "C", "K", "T", "P", "H"];
+const JAMO_V: [&str; 21] = ["A", "AE", "YA", "YAE", "EO", "E", "YEO", "YE", "O", "WA", "WAE", "OE", "YO", "U", "WEO", "WE", "WI",
+                            "YU", "EU", "YI", "I"];
+const JAMO_T: [&str; 28] = ["", "G", "GG", "GS", "N", "NJ", "NH", "D", "L", "LG", "LM", "LB", "LS", "LT", "LP", "LH", "M", "B",
+                            "BS", "S", "SS", "NG", "J", "C", "K", "T", "P", "H"];
+
+/// What can be worked out about a codepoint the index doesn't have one by one: a character the UCD only gives
+/// as part of a range, like CJK ideographs or private use, or one that isn't assigned at all
+pub fn unlisted(search: &Search, cp: u32) -> CharInfo {
+    let mut info = match search.ranges().iter().find(|r| (r.first..=r.last).contains(&cp)) {
+        Some(range) => range.char_info(cp),
+        None => CharInfo { cp, category: String::from("Cn"), script: String::from("Zzzz"), bidi_class: String::from("L"),
+                           grapheme_break: String::from("XX"), ..CharInfo::default() }
+    };
+    if (HANGUL_BASE..HANGUL_BASE + HANGUL_COUNT).contains(&cp) {
+        // the only range whose names aren't the codepoint
+        let s = cp - HANGUL_BASE;
+        info.name = format!("HANGUL SYLLABLE {}{}{}", JAMO_L[(s / 588) as usize], JAMO_V[(s % 588 / 28) as usize], JAMO_T[(s % 28) as usize]);
+        info.block = String::from("Hangul_Syllables");
+        info.category = String::from("Lo");
+        info.script = String::from("Hang");
+        info.grapheme_break = String::from(if s % 28 == 0 { "LV" } else { "LVT" });
+        info.age = String::from("2.0");
+    } else if info.name.is_empty() {
+        // the code point labels of the standard, for what has no name
+        match info.category.as_str() {
+            "Co" => info.name = format!("<private-use-{:04X}>", cp),
+            "Cs" => info.name = format!("<surrogate-{:04X}>", cp),
+            _ => {}
+        }
+    }
+    info
+}
+
+/// Look up every character of `text`
+pub fn char_infos(search: &Search, text: &str) -> Result<Vec<CharInfo>, Box<dyn Error>> {
+    text.chars().map(|c| Ok(search.lookup(c as u32)?.unwrap_or_else(|| unlisted(search, c as u32)))).collect()
+}
+
+/// Whether the extended grapheme cluster rules of UAX #29 put a boundary between `prev` and `next`.
+/// `ri_run` is how many regional indicators end at `prev`, `pict_zwj` is whether `prev` is a ZWJ
+/// following an extended pictographic character and `linked` is whether `prev` ends an Indic consonant
+/// followed by extenders and linkers, at least one of them a linker.
+fn is_boundary(prev: &CharInfo, next: &CharInfo, ri_run: usize, pict_zwj: bool, linked: bool) -> bool {
+    match (prev.grapheme_break.as_str(), next.grapheme_break.as_str()) {
+        ("CR", "LF") => false,
+        ("CN", _) | ("CR", _) | ("LF", _) | (_, "CN") | (_, "CR") | (_, "LF") => true,
+        ("L", "L") | ("L", "V") | ("L", "LV") | ("L", "LVT") => false,
+        ("LV", "V") | ("LV", "T") | ("V", "V") | ("V", "T") => false,
+        ("LVT", "T") | ("T", "T") => false,
+        (_, "EX") | (_, "ZWJ") | (_, "SM") => false,
+        ("PP", _) => false,
+        // consonants joined by a virama make one conjunct
+        _ if linked && next.indic_conjunct_break == "Consonant" => false,
+        ("ZWJ", _) if pict_zwj && next.extended_pictographic => false,
+        // flags are pairs of regional indicators
+        ("RI", "RI") => ri_run % 2 == 0,
+        _ => true
+    }
+}
+
+/// Split characters into extended grapheme clusters, what a reader would call a single character
+pub fn graphemes(chars: Vec<CharInfo>) -> Vec<Vec<CharInfo>> {
+    let mut clusters: Vec<Vec<CharInfo>> = Vec::new();
+    let mut ri_run = 0;
+    let mut pict = false; // inside an extended pictographic character and its extenders
+    let mut pict_zwj = false;
+    let mut consonant = false; // inside an Indic consonant and the extenders and linkers after it
+    let mut linked = false;
+    for c in chars {
+        let join = clusters.last().and_then(|g| g.last()).is_some_and(|prev| !is_boundary(prev, &c, ri_run, pict_zwj, linked));
+        ri_run = if c.grapheme_break == "RI" { ri_run + 1 } else { 0 };
+        pict_zwj = pict && c.grapheme_break == "ZWJ";
+        pict = c.extended_pictographic || (pict && c.grapheme_break == "EX");
+        linked = match c.indic_conjunct_break.as_str() {
+            "Linker" => consonant,
+            "Extend" => linked,
+            _ => false
+        };
+        consonant = match c.indic_conjunct_break.as_str() {
+            "Consonant" => true,
+            "Linker" | "Extend" => consonant,
+            _ => false
+        };
+        match clusters.last_mut() {
+            Some(g) if join => g.push(c),
+            _ => clusters.push(vec![c])
+        }
+    }
+    clusters
+}
+
+pub fn utf8_hex(c: char) -> String {
+    let mut buf = [0u8; 4];
+    c.encode_utf8(&mut buf).bytes().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(" ")
+}
+
+pub fn utf16_hex(c: char) -> String {
+    let mut buf = [0u16; 2];
+    c.encode_utf16(&mut buf).iter().map(|u| format!("{:04X}", u)).collect::<Vec<_>>().join(" ")
+}
+
+/// Show a grapheme cluster without letting it break the line or attach to what is before it
+pub fn display_cluster(cluster: &[CharInfo]) -> String {
+    let shown = cluster.iter()
+        .filter(|c| !matches!(c.category.as_str(), "Cc" | "Cs" | "Zl" | "Zp"))
+        .filter_map(CharInfo::chr)
+        .collect::<String>();
+    match cluster.first() {
+        _ if shown.is_empty() => cluster.iter().map(|c| format!("\u{27e8}{}\u{27e9}", c.short_name())).collect(),
+        Some(c) if c.category.starts_with('M') => format!("\u{25cc}{}", shown),
+        _ => shown
+    }
+}
+
+/// One row of the table: codepoint, category, combining class, encodings and name
+pub fn format_row(c: &CharInfo) -> String {
+    format!("{:<8} {:<2} {:>3}  {:<12} {:<10} {}", format!("U+{:04X}", c.cp), c.category, c.combining_class,
+            utf8_hex(c.chr().unwrap_or('\u{fffd}')), utf16_hex(c.chr().unwrap_or('\u{fffd}')), c.name)
+}
+
+/// `ununi describe [TEXT...]` prints the grapheme clusters and codepoints of its arguments, or of stdin when there are none
+pub fn run(config: &Option<TomlValue>, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let text = if args.is_empty() {
+        let mut text = String::new();
+        io::stdin().read_to_string(&mut text)?;
+        text
+    } else {
+        args.join(" ")
+    };
+    let search = Search::open(config)?;
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    writeln!(out, "  {:<8} {:<2} {:>3}  {:<12} {:<10} name", "cp", "gc", "ccc", "UTF-8", "UTF-16")?;
+    for cluster in graphemes(char_infos(&search, &text)?) {
+        writeln!(out, "{}", display_cluster(&cluster))?;
+        for c in &cluster {
+            writeln!(out, "  {}", format_row(c))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ::describe::*;
+    use ::search::tests::fixture;
+
+    const CHARS: &str = r#"<group blk="Diacriticals" gc="Mn" sc="Zinh" bc="NSM" GCB="EX">
+<char cp="0301" na="COMBINING ACUTE ACCENT" ccc="230"/>
+</group>
+<group blk="Punctuation" gc="Cf" sc="Zyyy" bc="BN" GCB="ZWJ">
+<char cp="200D" na="ZERO WIDTH JOINER"><name-alias alias="ZWJ" type="abbreviation"/></char>
+</group>
+<group blk="Misc_Pictographs" gc="So" sc="Zyyy" bc="ON" ExtPict="Y">
+<char cp="1F469" na="WOMAN"/>
+<char cp="1F4BB" na="PERSONAL COMPUTER"/>
+</group>
+<group blk="High_Surrogates" gc="Cs" sc="Zzzz" bc="L" age="2.0">
+<char first-cp="D800" last-cp="DB7F" na=""/>
+</group>
+<group blk="PUA" gc="Co" sc="Zzzz" bc="L" age="1.1">
+<char first-cp="E000" last-cp="F8FF" na=""/>
+</group>
+<group blk="Tangut" gc="Lo" sc="Tang" bc="L" age="9.0">
+<char first-cp="17000" last-cp="187F7" na="TANGUT IDEOGRAPH-#"/>
+</group>
+"#;
+
+    fn gcb(cp: u32, grapheme_break: &str) -> CharInfo {
+        CharInfo { cp, grapheme_break: grapheme_break.into(), extended_pictographic: grapheme_break == "EP", ..CharInfo::default() }
+    }
+
+    fn incb(cp: u32, grapheme_break: &str, indic_conjunct_break: &str) -> CharInfo {
+        CharInfo { indic_conjunct_break: indic_conjunct_break.into(), ..gcb(cp, grapheme_break) }
+    }
+
+    fn split(chars: Vec<CharInfo>) -> Vec<Vec<u32>> {
+        graphemes(chars).iter().map(|g| g.iter().map(|c| c.cp).collect()).collect()
+    }
+
+    #[test]
+    fn grapheme_rules() {
+        assert_eq!(split(vec![gcb(0xd, "CR"), gcb(0xa, "LF"), gcb(0xa, "LF")]), vec![vec![0xd, 0xa], vec![0xa]]);
+        // flags pair up
+        assert_eq!(split(vec![gcb(1, "RI"), gcb(2, "RI"), gcb(3, "RI"), gcb(4, "RI"), gcb(5, "RI")]),
+                   vec![vec![1, 2], vec![3, 4], vec![5]]);
+        // a ZWJ only joins pictographs
+        assert_eq!(split(vec![gcb(1, "EP"), gcb(2, "EX"), gcb(3, "ZWJ"), gcb(4, "EP"), gcb(5, "XX"), gcb(6, "ZWJ"), gcb(7, "EP")]),
+                   vec![vec![1, 2, 3, 4], vec![5, 6], vec![7]]);
+        assert_eq!(split(vec![gcb(1, "L"), gcb(2, "V"), gcb(3, "T"), gcb(4, "LVT"), gcb(5, "V")]), vec![vec![1, 2, 3], vec![4], vec![5]]);
+        // a virama between consonants, even with a nukta or ZWJ around it, makes a conjunct like क्ष
+        assert_eq!(split(vec![incb(1, "XX", "Consonant"), incb(2, "EX", "Linker"), incb(3, "XX", "Consonant"),
+                              incb(4, "EX", "Extend"), incb(5, "EX", "Linker"), incb(6, "ZWJ", "Extend"), incb(7, "XX", "Consonant")]),
+                   vec![vec![1, 2, 3, 4, 5, 6, 7]]);
+        // without one, or without a consonant before it, they stay apart
+        assert_eq!(split(vec![incb(1, "XX", "Consonant"), incb(2, "EX", "Extend"), incb(3, "XX", "Consonant"),
+                              incb(4, "XX", "None"), incb(5, "EX", "Linker"), incb(6, "XX", "Consonant")]),
+                   vec![vec![1, 2], vec![3], vec![4, 5], vec![6]]);
+    }
+
+    #[test]
+    fn names_ranges() {
+        let s = fixture(CHARS);
+        let tangut = unlisted(&s, 0x17001);
+        assert_eq!((tangut.name.as_str(), tangut.script.as_str(), tangut.block.as_str(), tangut.age.as_str()),
+                   ("TANGUT IDEOGRAPH-17001", "Tang", "Tangut", "9.0"));
+        assert_eq!(unlisted(&s, 0xe000).name, "<private-use-E000>");
+        assert_eq!(unlisted(&s, 0xd800).name, "<surrogate-D800>");
+        assert_eq!(unlisted(&s, 0xd55c).name, "HANGUL SYLLABLE HAN");
+        let reserved = unlisted(&s, 0x187f8);
+        assert_eq!((reserved.name.as_str(), reserved.category.as_str()), ("", "Cn"));
+    }
+
+    #[test]
+    fn describes_text() {
+        let s = fixture(CHARS);
+        let clusters = graphemes(char_infos(&s, "A\u{301}\u{1f469}\u{200d}\u{1f4bb}\u{d55c}").unwrap());
+        assert_eq!(clusters.iter().map(|g| g.len()).collect::<Vec<_>>(), vec![2, 3, 1]);
+        assert_eq!(format_row(&clusters[0][1]), "U+0301   Mn 230  CC 81        0301       COMBINING ACUTE ACCENT");
+        assert_eq!(utf16_hex('\u{1f469}'), "D83D DC69");
+        assert_eq!(clusters[2][0].name, "HANGUL SYLLABLE HAN");
+        assert_eq!(display_cluster(&clusters[0][1..]), "\u{25cc}\u{301}");
+        assert_eq!(display_cluster(&clusters[1]), "\u{1f469}\u{200d}\u{1f4bb}");
+    }
+}
@@ -0,0 +1,131 @@
+use std::error::Error;
+
+use toml::Value as TomlValue;
+
+use search::{Search, CharInfo};
+use confusables::Confusables;
+use describe::{unlisted, utf8_hex, utf16_hex};
+use dmenu::format_line;
+
+/// Characters as codepoints with their glyphs, ex. `É U+00C9`
+fn show_chars(s: &str) -> String {
+    s.chars().map(|c| format!("{} U+{:04X}", c, c as u32)).collect::<Vec<_>>().join(", ")
+}
+
+/// Everything there is to show about one character
+pub struct Detail {
+    /// labelled properties, leaving out the ones that don't apply
+    pub fields: Vec<(&'static str, String)>,
+    /// the other case, the parts of the decomposition and the characters it can be confused with
+    pub related: Vec<CharInfo>
+}
+
+impl Detail {
+    pub fn new(search: &Search, info: &CharInfo, confusables: Option<&Confusables>) -> Result<Detail, Box<dyn Error>> {
+        let c = info.chr().unwrap_or('\u{fffd}');
+        let mut fields = vec![("codepoint", format!("U+{:04X}", info.cp)), ("name", info.name.clone())];
+        if !info.aliases.is_empty() { fields.push(("aliases", info.aliases.join(", "))); }
+        fields.push(("block", info.block.clone()));
+        fields.push(("script", info.script.clone()));
+        fields.push(("category", info.category.clone()));
+        if !info.age.is_empty() { fields.push(("age", info.age.clone())); }
+        fields.push(("bidi class", info.bidi_class.clone()));
+        fields.push(("combining class", info.combining_class.to_string()));
+        if !info.decomposition.is_empty() {
+            fields.push(("decomposition", format!("<{}> {}", info.decomposition_type, show_chars(&info.decomposition))));
+        }
+        for &(label, mapped) in &[("uppercase", &info.uppercase), ("lowercase", &info.lowercase),
+                                  ("titlecase", &info.titlecase), ("case folding", &info.casefold)] {
+            if !mapped.is_empty() { fields.push((label, show_chars(mapped))); }
+        }
+        fields.push(("UTF-8", utf8_hex(c)));
+        fields.push(("UTF-16", utf16_hex(c)));
+        fields.push(("UTF-32", format!("{:08X}", info.cp)));
+
+        let mut related_chars = Vec::new();
+        for mapped in &[&info.uppercase, &info.lowercase] {
+            let mut chars = mapped.chars();
+            // only a simple case pair is a character of its own
+            if let (Some(other), None) = (chars.next(), chars.next()) { related_chars.push(other); }
+        }
+        related_chars.extend(info.decomposition.chars());
+        related_chars.extend(confusables.map(|conf| conf.confusable_with(c)).unwrap_or_default());
+        let mut related: Vec<CharInfo> = Vec::new();
+        for r in related_chars {
+            if r == c || related.iter().any(|o| o.cp == r as u32) { continue; }
+            related.push(search.lookup(r as u32)?.unwrap_or_else(|| unlisted(search, r as u32)));
+        }
+        Ok(Detail { fields, related })
+    }
+}
+
+/// `ununi info CHAR` prints everything known about a character, which can also be given as `U+XXXX`
+pub fn run(config: &Option<TomlValue>, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let arg = match args {
+        [a] => a.as_str(),
+        _ => return Err("usage: ununi info CHAR".into())
+    };
+    let mut chars = arg.chars();
+    let c = match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => arg.strip_prefix("U+").or_else(|| arg.strip_prefix("u+"))
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok()).and_then(::std::char::from_u32)
+    }.ok_or("usage: ununi info CHAR")?;
+    let search = Search::open(config)?;
+    let info = search.lookup(c as u32)?.unwrap_or_else(|| unlisted(&search, c as u32));
+    // confusables are a nice to have, not worth failing over when they can't be downloaded
+    let confusables = Confusables::open().ok();
+    let detail = Detail::new(&search, &info, confusables.as_ref())?;
+    for (label, value) in &detail.fields {
+        println!("{:<16} {}", label, value);
+    }
+    if !detail.related.is_empty() {
+        println!("related");
+        for r in &detail.related {
+            println!("  {}", format_line(r));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ::detail::*;
+    use ::search::tests::fixture;
+
+    const CHARS: &str = r#"<group blk="ASCII" gc="Ll" sc="Latn" bc="L" age="1.1">
+<char cp="0065" na="LATIN SMALL LETTER E" uc="0045" tc="0045"/>
+</group>
+<group blk="Latin_1_Sup" gc="Zs" sc="Zyyy" bc="CS">
+<char cp="00A0" na="NO-BREAK SPACE"><name-alias alias="NBSP" type="abbreviation"/></char>
+</group>
+<group blk="Latin_1_Sup" gc="Ll" sc="Latn" bc="L" age="1.1">
+<char cp="00E9" na="LATIN SMALL LETTER E WITH ACUTE" dt="can" dm="0065 0301" uc="00C9" tc="00C9"/>
+</group>
+<group blk="Diacriticals" gc="Mn" sc="Zinh" bc="NSM">
+<char cp="0301" na="COMBINING ACUTE ACCENT" ccc="230"/>
+</group>
+"#;
+
+    fn field<'a>(d: &'a Detail, label: &str) -> Option<&'a str> {
+        d.fields.iter().find(|f| f.0 == label).map(|f| f.1.as_str())
+    }
+
+    #[test]
+    fn details() {
+        let s = fixture(CHARS);
+        let e = s.lookup(0xe9).unwrap().unwrap();
+        let d = Detail::new(&s, &e, None).unwrap();
+        assert_eq!(field(&d, "decomposition"), Some("<can> e U+0065, \u{301} U+0301"));
+        assert_eq!(field(&d, "uppercase"), Some("\u{c9} U+00C9"));
+        assert_eq!(field(&d, "age"), Some("1.1"));
+        assert_eq!(field(&d, "UTF-8"), Some("C3 A9"));
+        assert_eq!(field(&d, "UTF-32"), Some("000000E9"));
+        assert_eq!(field(&d, "aliases"), None);
+        // É isn't in the fixture, so it is shown as well as can be without it
+        assert_eq!(d.related.iter().map(|r| r.cp).collect::<Vec<_>>(), vec![0xc9, 0x65, 0x301]);
+
+        let nbsp = s.lookup(0xa0).unwrap().unwrap();
+        assert_eq!(field(&Detail::new(&s, &nbsp, None).unwrap(), "aliases"), Some("NBSP"));
+    }
+}
@@ -0,0 +1,145 @@
+use std::error::Error;
+use std::io::{self, BufRead, Write};
+
+use toml::Value as TomlValue;
+
+use search::{Search, CharInfo};
+use formats::Format;
+use browse::Group;
+
+/// Compare block names the way UAX #44 loose matching does: ignoring case, spaces, hyphens and underscores
+pub fn loose_eq(a: &str, b: &str) -> bool {
+    let norm = |s: &str| s.chars().filter(|c| !(c.is_whitespace() || *c == '-' || *c == '_'))
+        .flat_map(char::to_lowercase).collect::<String>();
+    norm(a) == norm(b)
+}
+
+/// Which characters to list
+#[derive(Default)]
+pub struct Filter {
+    /// a block name from the UCD's block list, loosely matched
+    pub block: Option<String>,
+    /// a general category (`Sm`) or a major class (`S`)
+    pub category: Option<String>
+}
+
+impl Filter {
+    /// The characters to list in codepoint order: those of the block as `ununi browse --block` lists them,
+    /// or every character in the index, narrowed down to the category
+    pub fn chars(&self, search: &Search) -> Result<Vec<CharInfo>, Box<dyn Error>> {
+        let chars = match self.block {
+            Some(ref name) => {
+                let block = search.blocks().iter().find(|b| loose_eq(&b.name, name))
+                    .ok_or_else(|| format!("no block named {}", name))?;
+                Group::Block(block.clone()).chars(search, false)?
+            },
+            None => search.all_chars()?
+        };
+        Ok(chars.into_iter().filter(|c| self.matches(c)).collect())
+    }
+
+    /// Whether a character has the category
+    pub fn matches(&self, c: &CharInfo) -> bool {
+        self.category.iter().all(|gc| if gc.len() == 1 { c.category.starts_with(gc.as_str()) } else { c.category == *gc })
+    }
+}
+
+/// Format a character as a `glyph<TAB>U+XXXX<TAB>NAME<TAB>BLOCK` line
+pub fn format_line(c: &CharInfo) -> String {
+    let glyph = match (c.chr(), c.category.as_str()) {
+        // these would break the line or the terminal
+        (None, _) | (_, "Cc") | (_, "Cs") | (_, "Zl") | (_, "Zp") => String::new(),
+        // show combining marks on a dotted circle instead of whatever happens to be before them
+        (Some(ch), "Mn") | (Some(ch), "Mc") | (Some(ch), "Me") => format!("\u{25cc}{}", ch),
+        (Some(ch), _) => ch.to_string()
+    };
+    format!("{}\tU+{:04X}\t{}\t{}", glyph, c.cp, c.name, c.block)
+}
+
+/// Recover the character from a line picked out of `format_line`'s output. Lines that have been
+/// trimmed down to just the glyph by the picker also work.
+pub fn parse_line(line: &str) -> Option<char> {
+    line.split('\t')
+        .filter_map(|f| f.trim().strip_prefix("U+"))
+        .filter_map(|hex| u32::from_str_radix(hex, 16).ok())
+        .filter_map(::std::char::from_u32)
+        .next()
+        .or_else(|| line.trim_start_matches('\u{25cc}').chars().next())
+}
+
+/// `ununi dmenu [--block NAME] [--category GC]` lists characters for rofi, dmenu, fzf and friends,
+/// `ununi dmenu --pick [--format FORMAT]` turns the selected lines read from stdin back into characters
+pub fn run(config: &Option<TomlValue>, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut filter = Filter::default();
+    let mut pick = false;
+    let mut format = Format::Raw;
+    let mut args = args.iter();
+    while let Some(a) = args.next() {
+        match a.as_str() {
+            "--block" => filter.block = Some(args.next().ok_or("--block needs a block name")?.clone()),
+            "--category" => filter.category = Some(args.next().ok_or("--category needs a general category")?.clone()),
+            "--pick" => pick = true,
+            "--format" => format = args.next().and_then(|f| Format::parse(f))
+                .ok_or("--format needs one of: raw, codepoint, rust, json, python, html, percent, tex, name")?,
+            _ => return Err(format!("unknown argument {}; usage: ununi dmenu [--block NAME] [--category GC] [--pick [--format FORMAT]]", a).into())
+        }
+    }
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    if pick {
+        let stdin = io::stdin();
+        let mut picked = String::new();
+        for line in stdin.lock().lines() {
+            if let Some(c) = parse_line(&line?) {
+                picked.push(c);
+            }
+        }
+        if format != Format::Raw {
+            picked = format.apply(&picked, &Search::open(config)?)?;
+        }
+        write!(out, "{}", picked)?;
+    } else {
+        let search = Search::open(config)?;
+        for c in filter.chars(&search)? {
+            if let Err(e) = writeln!(out, "{}", format_line(&c)) {
+                // the picker closing its end early is fine
+                if e.kind() == io::ErrorKind::BrokenPipe { break; }
+                return Err(Box::new(e));
+            }
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ::dmenu::*;
+    use ::search::tests::fixture;
+
+    #[test]
+    fn lines_round_trip() {
+        let s = fixture("");
+        for c in s.all_chars().expect("listing characters") {
+            let line = format_line(&c);
+            assert_eq!(line.split('\t').count(), 4);
+            assert_eq!(parse_line(&line), c.chr());
+        }
+        assert_eq!(format_line(&s.all_chars().unwrap()[0]), "A\tU+0041\tLATIN CAPITAL LETTER A\tASCII");
+        assert_eq!(parse_line("λ"), Some('λ'));
+        assert_eq!(parse_line("\u{25cc}\u{301}"), Some('\u{301}'));
+    }
+
+    #[test]
+    fn filters() {
+        let s = fixture("");
+        let listed = |f: Filter| f.chars(&s).unwrap().iter().map(|c| c.cp).collect::<Vec<_>>();
+        assert_eq!(listed(Filter { block: Some("arrows".into()), category: None }), vec![0x2190, 0x2192]);
+        assert_eq!(listed(Filter { block: Some("basic_latin".into()), category: Some("Lu".into()) }), vec![0x41, 0x42]);
+        // the block's name in the UCD's block list, not the short alias the characters carry
+        assert!(Filter { block: Some("ASCII".into()), category: None }.chars(&s).is_err());
+        assert_eq!(listed(Filter { block: None, category: Some("L".into()) }), vec![0x41, 0x42, 0x3bb]);
+        assert_eq!(listed(Filter { block: None, category: Some("Ll".into()) }), vec![0x3bb]);
+    }
+}
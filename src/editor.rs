@@ -0,0 +1,191 @@
+/// The query text field: the text typed so far and a cursor into it.
+/// Every edit returns true if the text changed and the query needs to be rerun.
+#[derive(Default)]
+pub struct QueryEditor {
+    text: String,
+    cursor: usize // in chars, not bytes
+}
+
+impl QueryEditor {
+    pub fn new() -> QueryEditor {
+        QueryEditor::default()
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// The cursor position in UTF-16 code units, which is what DirectWrite and friends expect
+    pub fn cursor_utf16(&self) -> usize {
+        self.text.chars().take(self.cursor).map(char::len_utf16).sum()
+    }
+
+    fn byte_pos(&self, cursor: usize) -> usize {
+        self.text.char_indices().nth(cursor).map_or(self.text.len(), |(i, _)| i)
+    }
+
+    fn len(&self) -> usize {
+        self.text.chars().count()
+    }
+
+    pub fn insert(&mut self, c: char) -> bool {
+        if c.is_control() { return false; }
+        let at = self.byte_pos(self.cursor);
+        self.text.insert(at, c);
+        self.cursor += 1;
+        true
+    }
+
+    pub fn backspace(&mut self) -> bool {
+        if self.cursor == 0 { return false; }
+        self.cursor -= 1;
+        let at = self.byte_pos(self.cursor);
+        self.text.remove(at);
+        true
+    }
+
+    /// Delete the character under the cursor. At the end of the text this deletes the last
+    /// character instead, and at the very start it clears the whole query.
+    pub fn delete(&mut self) -> bool {
+        if self.cursor == 0 { return self.clear(); }
+        if self.cursor == self.len() { self.cursor -= 1; }
+        let at = self.byte_pos(self.cursor);
+        self.text.remove(at);
+        true
+    }
+
+    pub fn left(&mut self) {
+        if self.cursor > 0 { self.cursor -= 1; }
+    }
+
+    pub fn right(&mut self) {
+        if self.cursor < self.len() { self.cursor += 1; }
+    }
+
+    /// Whether the cursor is after the last character, where a completion can go
+    pub fn at_end(&self) -> bool {
+        self.cursor == self.len()
+    }
+
+    /// Replace the whole text, leaving the cursor at its end
+    pub fn set(&mut self, text: &str) -> bool {
+        let changed = self.text != text;
+        self.text = text.chars().filter(|c| !c.is_control()).collect();
+        self.cursor = self.len();
+        changed
+    }
+
+    pub fn clear(&mut self) -> bool {
+        let changed = !self.text.is_empty();
+        self.text.clear();
+        self.cursor = 0;
+        changed
+    }
+}
+
+/// Text picked so far, to be inserted all at once. Each pick is kept separately so it can be taken back whole.
+#[derive(Default)]
+pub struct ComposeBuffer {
+    pieces: Vec<String>
+}
+
+impl ComposeBuffer {
+    pub fn push(&mut self, piece: &str) {
+        if !piece.is_empty() { self.pieces.push(piece.to_owned()); }
+    }
+
+    /// Take back the last pick, returning false when there was none
+    pub fn pop(&mut self) -> bool {
+        self.pieces.pop().is_some()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pieces.is_empty()
+    }
+
+    pub fn text(&self) -> String {
+        self.pieces.concat()
+    }
+
+    /// The whole text, leaving the buffer empty
+    pub fn take(&mut self) -> String {
+        let text = self.text();
+        self.pieces.clear();
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::editor::*;
+
+    fn typed(s: &str) -> QueryEditor {
+        let mut e = QueryEditor::new();
+        for c in s.chars() { e.insert(c); }
+        e
+    }
+
+    #[test]
+    fn insert_at_cursor() {
+        let mut e = typed("arow");
+        e.left(); e.left();
+        assert!(e.insert('r'));
+        assert_eq!(e.text(), "arrow");
+        assert_eq!(e.cursor, 3);
+        assert!(!e.insert('\u{8}'));
+        e.right(); e.right(); e.right();
+        assert_eq!(e.cursor, 5);
+    }
+
+    #[test]
+    fn set_moves_to_end() {
+        let mut e = typed("arr");
+        e.left();
+        assert!(!e.at_end());
+        assert!(e.set("leftwards arrow "));
+        assert_eq!((e.cursor, e.at_end()), (16, true));
+        assert!(!e.set("leftwards arrow "));
+    }
+
+    #[test]
+    fn backspace_and_delete() {
+        let mut e = typed("lambda");
+        assert!(e.backspace());
+        assert_eq!(e.text(), "lambd");
+        assert!(e.delete());
+        assert_eq!(e.text(), "lamb");
+        e.left(); e.left();
+        assert!(e.delete());
+        assert_eq!(e.text(), "lab");
+        while e.cursor > 0 { e.left(); }
+        assert!(!e.backspace());
+        assert!(e.delete());
+        assert_eq!(e.text(), "");
+    }
+
+    #[test]
+    fn cursor_counts_chars() {
+        let mut e = typed("é→x");
+        e.left();
+        assert_eq!(e.cursor, 2);
+        assert_eq!(e.cursor_utf16(), 2);
+        assert!(e.backspace());
+        assert_eq!(e.text(), "éx");
+        let mut e = typed("😀a");
+        e.left();
+        assert_eq!(e.cursor_utf16(), 2);
+    }
+
+    #[test]
+    fn compose_buffer() {
+        let mut b = ComposeBuffer::default();
+        b.push("\u{2192}"); b.push(" "); b.push("\u{1d41a}\u{1d41b}"); b.push("");
+        assert_eq!(b.text(), "\u{2192} \u{1d41a}\u{1d41b}");
+        // a pick is taken back whole
+        assert!(b.pop());
+        assert_eq!(b.text(), "\u{2192} ");
+        assert_eq!(b.take(), "\u{2192} ");
+        assert!(b.is_empty());
+        assert!(!b.pop());
+    }
+}
@@ -0,0 +1,193 @@
+use std::error::Error;
+use std::io::{BufRead, BufReader};
+
+use search::data_file;
+
+const EMOJI_TEST_URL: &str = "https://www.unicode.org/Public/emoji/latest/emoji-test.txt";
+
+/// One fully-qualified emoji, which can be a sequence of several codepoints
+#[derive(Debug, Clone, PartialEq)]
+pub struct Emoji {
+    pub text: String,
+    /// the CLDR short name, ex. `grinning face` or `flag: Japan`
+    pub name: String,
+    /// the emoji version it was added in
+    pub version: String,
+    /// index into `Emojis::subgroups`
+    pub subgroup: usize
+}
+
+/// The emoji from emoji-test.txt, in its order, with the groups and subgroups it puts them in
+pub struct Emojis {
+    pub groups: Vec<String>,
+    /// the name of each subgroup, ex. `face-smiling`, and the index of the group it is in
+    pub subgroups: Vec<(String, usize)>,
+    pub emoji: Vec<Emoji>
+}
+
+impl Emojis {
+    /// Read emoji-test.txt, keeping only the fully-qualified sequences and the groups that have any
+    pub fn parse<R: BufRead>(r: R) -> Result<Emojis, Box<dyn Error>> {
+        let mut groups: Vec<String> = Vec::new();
+        let mut subgroups: Vec<(String, usize)> = Vec::new();
+        let mut emoji = Vec::new();
+        for line in r.lines() {
+            let line = line?;
+            if let Some(group) = line.strip_prefix("# group:") {
+                groups.push(group.trim().to_owned());
+                continue;
+            }
+            if let Some(subgroup) = line.strip_prefix("# subgroup:") {
+                if groups.is_empty() { return Err("subgroup before any group in emoji-test.txt".into()); }
+                subgroups.push((subgroup.trim().to_owned(), groups.len() - 1));
+                continue;
+            }
+            let (data, comment) = match line.find('#') {
+                Some(i) => (&line[..i], &line[i + 1..]),
+                None => continue
+            };
+            let mut fields = data.split(';');
+            let (codepoints, status) = match (fields.next(), fields.next()) {
+                (Some(codepoints), Some(status)) => (codepoints, status.trim()),
+                _ => continue
+            };
+            if status != "fully-qualified" { continue; }
+            let text = codepoints.split_whitespace()
+                .map(|hex| u32::from_str_radix(hex, 16).ok().and_then(::std::char::from_u32))
+                .collect::<Option<String>>().ok_or("bad codepoint in emoji-test.txt")?;
+            // the comment is the emoji, its version like `E1.0` and its name
+            let mut comment = comment.trim().splitn(3, ' ').skip(1);
+            let version = comment.next().unwrap_or("").trim_start_matches('E').to_owned();
+            let name = comment.next().unwrap_or("").to_owned();
+            if subgroups.is_empty() { return Err("emoji before any subgroup in emoji-test.txt".into()); }
+            emoji.push(Emoji { text, name, version, subgroup: subgroups.len() - 1 });
+        }
+        Ok(Emojis { groups, subgroups, emoji }.without_empty())
+    }
+
+    /// Drop the groups and subgroups that were left without any emoji, like the one with only skin tone components
+    fn without_empty(mut self) -> Emojis {
+        let used_subgroups = self.subgroups.iter().enumerate()
+            .map(|(i, _)| self.emoji.iter().any(|e| e.subgroup == i)).collect::<Vec<_>>();
+        let used_groups = self.groups.iter().enumerate()
+            .map(|(g, _)| self.subgroups.iter().enumerate().any(|(i, s)| s.1 == g && used_subgroups[i])).collect::<Vec<_>>();
+        let renumber = |used: &[bool]| used.iter().scan(0, |n, &u| { let i = *n; if u { *n += 1; } Some(i) }).collect::<Vec<_>>();
+        let (subgroup_index, group_index) = (renumber(&used_subgroups), renumber(&used_groups));
+        self.groups = self.groups.into_iter().zip(&used_groups).filter(|g| *g.1).map(|g| g.0).collect();
+        self.subgroups = self.subgroups.into_iter().zip(&used_subgroups).filter(|s| *s.1)
+            .map(|((name, g), _)| (name, group_index[g])).collect();
+        for e in &mut self.emoji {
+            e.subgroup = subgroup_index[e.subgroup];
+        }
+        self
+    }
+
+    /// Load emoji-test.txt from the current directory, downloading it if it isn't there
+    pub fn open() -> Result<Emojis, Box<dyn Error>> {
+        Emojis::parse(BufReader::new(data_file("./emoji-test.txt", EMOJI_TEST_URL)?))
+    }
+
+    pub fn group_of(&self, e: &Emoji) -> usize {
+        self.subgroups[e.subgroup].1
+    }
+
+    /// The indices of the emoji in a group, or in all of them, with a word of their name or subgroup starting with
+    /// every word of the query
+    pub fn filter(&self, group: Option<usize>, query: &str) -> Vec<usize> {
+        let words = query.split_whitespace().map(str::to_lowercase).collect::<Vec<_>>();
+        self.emoji.iter().enumerate()
+            .filter(|(_, e)| group.map_or(true, |g| self.group_of(e) == g))
+            .filter(|(_, e)| {
+                let name = e.name.to_lowercase();
+                let name_words = name.split([' ', ':', ',', '-']).chain(self.subgroups[e.subgroup].0.split('-')).collect::<Vec<_>>();
+                words.iter().all(|w| name_words.iter().any(|n| n.starts_with(w.as_str())))
+            })
+            .map(|(i, _)| i).collect()
+    }
+}
+
+/// `ununi emoji` lists the emoji groups, `ununi emoji [--group NAME] QUERY...` the emoji whose names match the query,
+/// in that group or in all of them, under their subgroups
+pub fn run(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut group = None;
+    let mut words = Vec::new();
+    let mut args = args.iter();
+    while let Some(a) = args.next() {
+        match a.as_str() {
+            "--group" => group = Some(args.next().ok_or("usage: ununi emoji [--group NAME] [QUERY...]")?),
+            _ => words.push(a.as_str())
+        }
+    }
+    let emojis = Emojis::open()?;
+    if group.is_none() && words.is_empty() {
+        for (g, name) in emojis.groups.iter().enumerate() {
+            println!("{} ({} emoji)", name, emojis.filter(Some(g), "").len());
+        }
+        return Ok(());
+    }
+    let group = match group {
+        Some(name) => Some(emojis.groups.iter().position(|g| g.eq_ignore_ascii_case(name))
+                           .ok_or_else(|| format!("no emoji group named {}", name))?),
+        None => None
+    };
+    let mut subgroup = None;
+    for i in emojis.filter(group, &words.join(" ")) {
+        let e = &emojis.emoji[i];
+        if subgroup != Some(e.subgroup) {
+            println!("{}", emojis.subgroups[e.subgroup].0);
+            subgroup = Some(e.subgroup);
+        }
+        println!("  {}\t{}", e.text, e.name);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ::emoji::*;
+
+    const SAMPLE: &str = "# emoji-test.txt
+# group: Smileys & Emotion
+
+# subgroup: face-smiling
+1F600                                      ; fully-qualified     # \u{1f600} E1.0 grinning face
+1F603                                      ; fully-qualified     # \u{1f603} E0.6 grinning face with big eyes
+
+# subgroup: face-affection
+263A FE0F                                  ; fully-qualified     # \u{263a}\u{fe0f} E0.6 smiling face
+263A                                       ; unqualified         # \u{263a} E0.6 smiling face
+
+# group: Component
+
+# subgroup: skin-tone
+1F3FB                                      ; component           # \u{1f3fb} E1.0 light skin tone
+
+# group: Flags
+
+# subgroup: country-flag
+1F1EF 1F1F5                                ; fully-qualified     # \u{1f1ef}\u{1f1f5} E0.6 flag: Japan
+";
+
+    #[test]
+    fn parses() {
+        let e = Emojis::parse(SAMPLE.as_bytes()).unwrap();
+        assert_eq!(e.groups, vec!["Smileys & Emotion", "Flags"]);
+        assert_eq!(e.subgroups.iter().map(|s| (s.0.as_str(), s.1)).collect::<Vec<_>>(),
+                   vec![("face-smiling", 0), ("face-affection", 0), ("country-flag", 1)]);
+        assert_eq!(e.emoji.len(), 4);
+        assert_eq!(e.emoji[2], Emoji { text: "\u{263a}\u{fe0f}".to_owned(), name: "smiling face".to_owned(), version: "0.6".to_owned(), subgroup: 1 });
+        assert_eq!(e.emoji[3].text, "\u{1f1ef}\u{1f1f5}");
+        assert_eq!(e.group_of(&e.emoji[3]), 1);
+    }
+
+    #[test]
+    fn filters_within_group() {
+        let e = Emojis::parse(SAMPLE.as_bytes()).unwrap();
+        assert_eq!(e.filter(Some(0), ""), vec![0, 1, 2]);
+        assert_eq!(e.filter(Some(0), "grin big"), vec![1]);
+        // the subgroup counts as part of the name
+        assert_eq!(e.filter(Some(0), "affection"), vec![2]);
+        assert_eq!(e.filter(Some(1), "face"), Vec::<usize>::new());
+        assert_eq!(e.filter(None, "japan"), vec![3]);
+    }
+}
@@ -0,0 +1,154 @@
+use std::error::Error;
+
+use search::Search;
+use describe::unlisted;
+
+/// TeX commands for the characters that have a well known one, the rest go through `\symbol`
+const TEX: &[(char, &str)] = &[
+    ('α', "\\alpha"), ('β', "\\beta"), ('γ', "\\gamma"), ('δ', "\\delta"), ('ε', "\\epsilon"), ('ζ', "\\zeta"),
+    ('η', "\\eta"), ('θ', "\\theta"), ('ι', "\\iota"), ('κ', "\\kappa"), ('λ', "\\lambda"), ('μ', "\\mu"),
+    ('ν', "\\nu"), ('ξ', "\\xi"), ('π', "\\pi"), ('ρ', "\\rho"), ('σ', "\\sigma"), ('τ', "\\tau"),
+    ('υ', "\\upsilon"), ('φ', "\\phi"), ('χ', "\\chi"), ('ψ', "\\psi"), ('ω', "\\omega"),
+    ('Γ', "\\Gamma"), ('Δ', "\\Delta"), ('Θ', "\\Theta"), ('Λ', "\\Lambda"), ('Ξ', "\\Xi"), ('Π', "\\Pi"),
+    ('Σ', "\\Sigma"), ('Υ', "\\Upsilon"), ('Φ', "\\Phi"), ('Ψ', "\\Psi"), ('Ω', "\\Omega"),
+    ('←', "\\leftarrow"), ('→', "\\rightarrow"), ('↑', "\\uparrow"), ('↓', "\\downarrow"), ('↔', "\\leftrightarrow"),
+    ('⇐', "\\Leftarrow"), ('⇒', "\\Rightarrow"), ('⇔', "\\Leftrightarrow"), ('↦', "\\mapsto"),
+    ('∀', "\\forall"), ('∃', "\\exists"), ('∅', "\\emptyset"), ('∈', "\\in"), ('∉', "\\notin"), ('∋', "\\ni"),
+    ('⊂', "\\subset"), ('⊃', "\\supset"), ('⊆', "\\subseteq"), ('⊇', "\\supseteq"), ('∪', "\\cup"), ('∩', "\\cap"),
+    ('∧', "\\wedge"), ('∨', "\\vee"), ('¬', "\\neg"), ('∞', "\\infty"), ('∂', "\\partial"), ('∇', "\\nabla"),
+    ('∑', "\\sum"), ('∏', "\\prod"), ('∫', "\\int"), ('√', "\\surd"), ('∘', "\\circ"), ('·', "\\cdot"),
+    ('×', "\\times"), ('÷', "\\div"), ('±', "\\pm"), ('∓', "\\mp"), ('≤', "\\leq"), ('≥', "\\geq"),
+    ('≠', "\\neq"), ('≈', "\\approx"), ('≡', "\\equiv"), ('∼', "\\sim"), ('≅', "\\cong"), ('∝', "\\propto"),
+    ('⊕', "\\oplus"), ('⊗', "\\otimes"), ('⊥', "\\perp"), ('∥', "\\parallel"), ('⊢', "\\vdash"), ('⊨', "\\models"),
+    ('ℕ', "\\mathbb{N}"), ('ℤ', "\\mathbb{Z}"), ('ℚ', "\\mathbb{Q}"), ('ℝ', "\\mathbb{R}"), ('ℂ', "\\mathbb{C}"),
+    ('ℓ', "\\ell"), ('ℵ', "\\aleph"), ('…', "\\ldots"), ('—', "---"), ('–', "--"), ('§', "\\S"), ('¶', "\\P"),
+    ('©', "\\copyright"), ('£', "\\pounds"), ('†', "\\dag"), ('‡', "\\ddag"), ('°', "^\\circ")
+];
+
+/// How picked text is inserted
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Raw,
+    Codepoint,
+    Rust,
+    Json,
+    Python,
+    Html,
+    Percent,
+    Tex,
+    Name
+}
+
+/// Every format, in the order Ctrl+O cycles through them
+pub const FORMATS: [Format; 9] = [Format::Raw, Format::Codepoint, Format::Rust, Format::Json, Format::Python,
+                                  Format::Html, Format::Percent, Format::Tex, Format::Name];
+
+impl Format {
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Raw => "raw",
+            Format::Codepoint => "codepoint",
+            Format::Rust => "rust",
+            Format::Json => "json",
+            Format::Python => "python",
+            Format::Html => "html",
+            Format::Percent => "percent",
+            Format::Tex => "tex",
+            Format::Name => "name"
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Format> {
+        FORMATS.iter().cloned().find(|f| f.name().eq_ignore_ascii_case(s))
+    }
+
+    /// Write out `text` in this format. The names Python escapes and the name format need are looked up in `search`.
+    pub fn apply(self, text: &str, search: &Search) -> Result<String, Box<dyn Error>> {
+        let name = |c: char| -> Result<String, Box<dyn Error>> {
+            let info = search.lookup(c as u32)?.unwrap_or_else(|| unlisted(search, c as u32));
+            // controls have no name, only aliases
+            Ok(if info.name.is_empty() { info.aliases.first().cloned().unwrap_or_default() } else { info.name })
+        };
+        let mut out = String::new();
+        let mut after_command = false;
+        for (i, c) in text.chars().enumerate() {
+            match self {
+                Format::Raw => out.push(c),
+                Format::Codepoint => {
+                    if i > 0 { out.push(' '); }
+                    out += &format!("U+{:04X}", c as u32);
+                },
+                Format::Rust => out += &format!("\\u{{{:X}}}", c as u32),
+                Format::Json => for u in c.encode_utf16(&mut [0; 2]) {
+                    out += &format!("\\u{:04X}", u);
+                },
+                Format::Python => match name(c)? {
+                    // `<private-use-E000>` and the like are labels, not names \N{} knows
+                    ref n if !n.is_empty() && !n.starts_with('<') => out += &format!("\\N{{{}}}", n),
+                    _ if (c as u32) < 0x10000 => out += &format!("\\u{:04x}", c as u32),
+                    _ => out += &format!("\\U{:08x}", c as u32)
+                },
+                Format::Html => out += &format!("&#x{:X};", c as u32),
+                Format::Percent => for b in c.encode_utf8(&mut [0; 4]).bytes() {
+                    out += &format!("%{:02X}", b);
+                },
+                Format::Tex => {
+                    // a command that ends in a letter would run into a letter after it
+                    if after_command && c.is_ascii_alphabetic() {
+                        out.push(' ');
+                    }
+                    after_command = false;
+                    match TEX.iter().find(|&&(t, _)| t == c) {
+                        Some(&(_, cmd)) => {
+                            out += cmd;
+                            after_command = cmd.starts_with('\\') && cmd.ends_with(|l: char| l.is_ascii_alphabetic());
+                        },
+                        None if c.is_ascii() => out.push(c),
+                        None => out += &format!("\\symbol{{\"{:X}}}", c as u32)
+                    }
+                },
+                Format::Name => {
+                    if i > 0 { out += ", "; }
+                    out += &name(c)?;
+                }
+            }
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::formats::*;
+    use ::search::tests::fixture;
+
+    const CHARS: &str = r#"<group blk="Latin_1_Sup" gc="Zs" sc="Zyyy" bc="CS">
+<char cp="00A0" na="NO-BREAK SPACE"/>
+</group>
+<group blk="PUA" gc="Co" sc="Zzzz" bc="L" age="1.1">
+<char first-cp="E000" last-cp="F8FF" na=""/>
+</group>
+<group blk="Sup_PUA_A" gc="Co" sc="Zzzz" bc="L" age="2.0">
+<char first-cp="F0000" last-cp="FFFFD" na=""/>
+</group>
+"#;
+
+    #[test]
+    fn formats() {
+        let s = fixture(CHARS);
+        let f = |format: Format, text| format.apply(text, &s).unwrap();
+        assert_eq!(f(Format::Raw, "λ→"), "λ→");
+        assert_eq!(f(Format::Codepoint, "λ→"), "U+03BB U+2192");
+        assert_eq!(f(Format::Rust, "λ\u{1f469}"), "\\u{3BB}\\u{1F469}");
+        assert_eq!(f(Format::Json, "λ\u{1f469}"), "\\u03BB\\uD83D\\uDC69");
+        assert_eq!(f(Format::Python, "\u{a0}\u{e000}"), "\\N{NO-BREAK SPACE}\\ue000");
+        assert_eq!(f(Format::Python, "\u{f0041}"), "\\U000f0041");
+        assert_eq!(f(Format::Name, "\u{e000}"), "<private-use-E000>");
+        assert_eq!(f(Format::Html, "→"), "&#x2192;");
+        assert_eq!(f(Format::Percent, "é"), "%C3%A9");
+        assert_eq!(f(Format::Tex, "λx→\u{1f469}"), "\\lambda x\\rightarrow\\symbol{\"1F469}");
+        assert_eq!(f(Format::Tex, "ab"), "ab");
+        assert_eq!(f(Format::Name, "A\u{d55c}"), "LATIN CAPITAL LETTER A, HANGUL SYLLABLE HAN");
+        assert_eq!(Format::parse("JSON"), Some(Format::Json));
+    }
+}
@@ -0,0 +1,106 @@
+use toml::Value as TomlValue;
+
+use tantivy::schema::{Field, Term, IndexRecordOption};
+use tantivy::query::{BooleanQuery, FuzzyTermQuery, TermQuery, Occur, Query};
+
+use rank::query_words;
+
+/// How forgiving the search is of typos, from the `[fuzzy]` table of the configuration. Names with words a few
+/// edits away from the words of the query are only looked for when the query itself finds too little.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fuzzy {
+    pub enabled: bool,
+    /// typos are looked for when the query finds fewer results than this
+    pub min_hits: usize,
+    /// the most edits a word can be off by, Tantivy can do at most 2
+    pub max_distance: u8,
+    /// shorter words have to be right, or too much would match them
+    pub min_length: usize
+}
+
+impl Default for Fuzzy {
+    fn default() -> Fuzzy {
+        Fuzzy { enabled: true, min_hits: 5, max_distance: 2, min_length: 4 }
+    }
+}
+
+impl Fuzzy {
+    pub fn from_config(config: &Option<TomlValue>) -> Fuzzy {
+        let mut fuzzy = Fuzzy::default();
+        let table = match config.as_ref().and_then(|c| c.get("fuzzy")) { Some(t) => t, None => return fuzzy };
+        let number = |key| table.get(key).and_then(TomlValue::as_integer).filter(|&n| n >= 0);
+        if let Some(enabled) = table.get("enabled").and_then(TomlValue::as_bool) { fuzzy.enabled = enabled; }
+        if let Some(n) = number("min_hits") { fuzzy.min_hits = n as usize; }
+        if let Some(n) = number("max_distance") { fuzzy.max_distance = n.min(2) as u8; }
+        if let Some(n) = number("min_length") { fuzzy.min_length = n as usize; }
+        fuzzy
+    }
+
+    /// Whether a query that found `hits` results should be tried again with typos
+    pub fn wanted(&self, hits: usize) -> bool {
+        self.enabled && hits < self.min_hits
+    }
+
+    /// How many edits `word` may be off by: one once it is `min_length` long, two at twice that
+    pub fn distance(&self, word: &str) -> u8 {
+        let len = word.chars().count();
+        let distance = if len < self.min_length { 0 } else if len < 2 * self.min_length { 1 } else { 2 };
+        distance.min(self.max_distance)
+    }
+
+    /// A query for names with a word close to any word of `query`, or None when none of them is long enough for that.
+    /// Only plain words are made fuzzy, queries with fields or operators mean exactly what they say.
+    pub fn query(&self, name: Field, query: &str) -> Option<BooleanQuery> {
+        let plain = query.split_whitespace().all(|t| t.chars().all(|c| c.is_alphanumeric() || c == '-')
+                                                 && !["AND", "OR", "NOT"].contains(&t) && !t.starts_with('-'));
+        let words = query_words(query);
+        if !plain || !words.iter().any(|w| self.distance(w) > 0) { return None; }
+        let clauses = words.iter().map(|w| {
+            let term = Term::from_field_text(name, w);
+            let q: Box<dyn Query> = match self.distance(w) {
+                0 => Box::new(TermQuery::new(term, IndexRecordOption::WithFreqs)),
+                d => Box::new(FuzzyTermQuery::new(term, d, true))
+            };
+            (Occur::Should, q)
+        }).collect::<Vec<_>>();
+        Some(BooleanQuery::from(clauses))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::fuzzy::*;
+    use ::search::tests::fixture;
+
+    #[test]
+    fn distances_and_config() {
+        let f = Fuzzy::default();
+        assert_eq!((f.distance("arw"), f.distance("arow"), f.distance("ellipses")), (0, 1, 2));
+        assert!(f.wanted(0) && !f.wanted(5));
+        let config = Some("[fuzzy]\nmax_distance = 1\nmin_hits = 1".parse::<TomlValue>().unwrap());
+        let f = Fuzzy::from_config(&config);
+        assert_eq!(f, Fuzzy { enabled: true, min_hits: 1, max_distance: 1, min_length: 4 });
+        assert_eq!(f.distance("ellipses"), 1);
+    }
+
+    #[test]
+    fn finds_typos() {
+        let mut s = fixture("");
+        let cps = |q| s.query(q, 10).unwrap().iter().map(|c| c.cp).collect::<Vec<_>>();
+        let mut arow = cps("arow");
+        arow.sort();
+        assert_eq!(arow, vec![0x2190, 0x2192]);
+        // and they are all that is counted
+        assert_eq!(s.query_page("arow", 1, 1).unwrap().total, 2);
+        assert_eq!(cps("lamdba"), vec![0x3bb]);
+        // what the query finds without typos comes first
+        assert_eq!(cps("leftwards arow"), vec![0x2190, 0x2192]);
+        // and words that are too short to be fuzzy aren't, nor queries that aren't just words
+        assert!(cps("arw").is_empty());
+        assert!(cps("arow AND blck:arrows").is_empty());
+
+        s.set_fuzzy(Fuzzy { enabled: false, ..Fuzzy::default() });
+        assert!(s.query("arow", 10).unwrap().is_empty());
+        assert_eq!(s.query_page("arow", 0, 10).unwrap().total, 0);
+    }
+}
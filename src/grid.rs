@@ -0,0 +1,141 @@
+use std::error::Error;
+
+use toml::Value as TomlValue;
+
+use search::{Search, CharInfo};
+use describe::display_cluster;
+
+/// Results laid out as glyphs in rows and columns, to scan a lot of them at a glance
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Grid {
+    pub columns: usize,
+    /// how many rows are shown at once, a page
+    pub rows: usize
+}
+
+impl Grid {
+    /// The grid from the `[grid]` table of the configuration, and whether to start out showing it
+    pub fn from_config(config: &Option<TomlValue>) -> (Grid, bool) {
+        let table = config.as_ref().and_then(|c| c.get("grid"));
+        let size = |key, default| table.and_then(|t| t.get(key)).and_then(TomlValue::as_integer)
+            .filter(|&n| n > 0).map_or(default, |n| n as usize);
+        let enabled = table.and_then(|t| t.get("enabled")).and_then(TomlValue::as_bool).unwrap_or(false);
+        (Grid { columns: size("columns", 12), rows: size("rows", 8) }, enabled)
+    }
+
+    pub fn page_size(&self) -> usize {
+        self.columns * self.rows
+    }
+
+    /// Lines of text with the glyphs of `chars` in rows, the one at `sel` in brackets
+    pub fn render_text(&self, chars: &[CharInfo], sel: Option<usize>) -> Vec<String> {
+        chars.chunks(self.columns).enumerate().map(|(r, row)| {
+            row.iter().enumerate().map(|(c, info)| {
+                let glyph = display_cluster(::std::slice::from_ref(info));
+                if sel == Some(r * self.columns + c) { format!("[{}]", glyph) } else { format!(" {} ", glyph) }
+            }).collect::<String>().trim_end().to_owned()
+        }).collect()
+    }
+}
+
+/// A direction to move the selection around the grid
+#[cfg(any(windows, test))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Move {
+    Left,
+    Right,
+    Up,
+    Down,
+    PageUp,
+    PageDown
+}
+
+#[cfg(any(windows, test))]
+impl Grid {
+    /// The first item on the page `sel` is on
+    pub fn page_start(&self, sel: usize) -> usize {
+        sel - sel % self.page_size()
+    }
+
+    /// Where the selection ends up after a move, staying inside the `count` items
+    pub fn step(&self, sel: usize, count: usize, mv: Move) -> usize {
+        if count == 0 { return 0; }
+        let last = count - 1;
+        match mv {
+            Move::Left => sel.saturating_sub(1),
+            Move::Right => (sel + 1).min(last),
+            Move::Up if sel >= self.columns => sel - self.columns,
+            Move::Up => sel,
+            // moving down from the row above a short last row lands on its last item
+            Move::Down if sel / self.columns < last / self.columns => (sel + self.columns).min(last),
+            Move::Down => sel,
+            Move::PageUp => sel.saturating_sub(self.page_size()),
+            Move::PageDown => (sel + self.page_size()).min(last)
+        }
+    }
+}
+
+/// `ununi grid [--columns N] [--rows N] QUERY...` prints the glyphs of the first page of results in a grid,
+/// and how many were left out
+pub fn run(config: &Option<TomlValue>, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let (mut grid, _) = Grid::from_config(config);
+    let mut words = Vec::new();
+    let mut args = args.iter();
+    while let Some(a) = args.next() {
+        match a.as_str() {
+            "--columns" => grid.columns = args.next().and_then(|n| n.parse().ok()).filter(|&n| n > 0)
+                .ok_or("--columns needs a number of columns")?,
+            "--rows" => grid.rows = args.next().and_then(|n| n.parse().ok()).filter(|&n| n > 0)
+                .ok_or("--rows needs a number of rows")?,
+            _ => words.push(a.as_str())
+        }
+    }
+    let page = Search::open(config)?.query_page(&words.join(" "), 0, grid.page_size())?;
+    for line in grid.render_text(&page.chars, None) {
+        println!("{}", line);
+    }
+    if page.total > page.chars.len() {
+        eprintln!("{} of {} results", page.chars.len(), page.total);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ::grid::*;
+    use ::search::tests::fixture;
+
+    #[test]
+    fn renders_text() {
+        let s = fixture("");
+        let chars = s.all_chars().unwrap();
+        let g = Grid { columns: 3, rows: 1 };
+        let lines = g.render_text(&chars[..5], Some(1));
+        assert_eq!(lines, vec![" A [B] \u{3bb}", " \u{2190}  \u{2192}"]);
+    }
+
+    #[test]
+    fn config() {
+        let config = Some("[grid]\nenabled = true\ncolumns = 16".parse::<TomlValue>().unwrap());
+        assert_eq!(Grid::from_config(&config), (Grid { columns: 16, rows: 8 }, true));
+        assert_eq!(Grid::from_config(&None), (Grid { columns: 12, rows: 8 }, false));
+    }
+
+    #[test]
+    fn grid_moves() {
+        let g = Grid { columns: 4, rows: 2 };
+        // 10 items: two full rows and a row of two
+        assert_eq!(g.step(0, 10, Move::Left), 0);
+        assert_eq!(g.step(3, 10, Move::Right), 4);
+        assert_eq!(g.step(9, 10, Move::Right), 9);
+        assert_eq!(g.step(5, 10, Move::Up), 1);
+        assert_eq!(g.step(1, 10, Move::Up), 1);
+        assert_eq!(g.step(7, 10, Move::Down), 9);
+        assert_eq!(g.step(9, 10, Move::Down), 9);
+        assert_eq!(g.step(1, 10, Move::PageDown), 9);
+        assert_eq!(g.step(9, 10, Move::PageUp), 1);
+        assert_eq!(g.step(0, 0, Move::Down), 0);
+        assert_eq!(g.page_start(9), 8);
+        assert_eq!(g.page_start(7), 0);
+    }
+}
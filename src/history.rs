@@ -0,0 +1,79 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufReader, ErrorKind as IOErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+const HISTORY_LEN: usize = 100;
+
+/// Recently inserted characters, most recent first. This is stored in a file shared by every
+/// frontend so it is reread before each change.
+pub struct History {
+    path: PathBuf,
+    entries: Vec<char>
+}
+
+fn read_entries(path: &Path) -> Result<Vec<char>, Box<dyn Error>> {
+    match File::open(path) {
+        Ok(f) => {
+            let mut entries = Vec::new();
+            for line in BufReader::new(f).lines() {
+                if let Some(c) = u32::from_str_radix(line?.trim(), 16).ok().and_then(::std::char::from_u32) {
+                    entries.push(c);
+                }
+            }
+            Ok(entries)
+        },
+        Err(e) => match e.kind() {
+            IOErrorKind::NotFound => Ok(Vec::new()),
+            _ => Err(Box::new(e))
+        }
+    }
+}
+
+impl History {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<History, Box<dyn Error>> {
+        let path = path.as_ref().to_owned();
+        let entries = read_entries(&path)?;
+        Ok(History { path, entries })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn entries(&self) -> &[char] {
+        &self.entries
+    }
+
+    /// Move `c` to the front of the history and save it
+    pub fn record(&mut self, c: char) -> Result<(), Box<dyn Error>> {
+        self.entries = read_entries(&self.path)?;
+        self.entries.retain(|&e| e != c);
+        self.entries.insert(0, c);
+        self.entries.truncate(HISTORY_LEN);
+        let mut f = File::create(&self.path)?;
+        for e in &self.entries {
+            writeln!(f, "{:04X}", *e as u32)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::history::*;
+
+    #[test]
+    fn record_moves_to_front_and_persists() {
+        let path = ::std::env::temp_dir().join(format!("ununi-history-test-{}", ::std::process::id()));
+        let _ = ::std::fs::remove_file(&path);
+        let mut h = History::open(&path).expect("opening history");
+        assert!(h.entries().is_empty());
+        h.record('→').unwrap();
+        h.record('λ').unwrap();
+        h.record('→').unwrap();
+        assert_eq!(h.entries(), &['→', 'λ']);
+        assert_eq!(History::open(&path).unwrap().entries(), &['→', 'λ']);
+        ::std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,296 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::io::{self, Read, Write};
+
+use toml::Value as TomlValue;
+
+use search::{Search, CharInfo};
+use confusables::Confusables;
+use normalize::Normalizer;
+
+fn is_private_use(cp: u32) -> bool {
+    matches!(cp, 0xE000..=0xF8FF | 0xF0000..=0xFFFFD | 0x100000..=0x10FFFD)
+}
+
+/// Scripts that are normally written together are treated as one
+fn script_group(sc: &str) -> &str {
+    match sc {
+        "Hira" | "Kana" | "Hani" | "Hang" | "Bopo" => "Hani",
+        _ => sc
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Bidi,
+    Invisible,
+    Lookalike,
+    MixedScript,
+    Unassigned,
+    PrivateUse
+}
+
+impl Kind {
+    pub fn id(self) -> &'static str {
+        match self {
+            Kind::Bidi => "bidi",
+            Kind::Invisible => "invisible",
+            Kind::Lookalike => "lookalike",
+            Kind::MixedScript => "mixed-script",
+            Kind::Unassigned => "unassigned",
+            Kind::PrivateUse => "private-use"
+        }
+    }
+
+    /// Bidi controls can make code run differently from how it reads, everything else is only suspicious
+    pub fn severity(self) -> &'static str {
+        match self {
+            Kind::Bidi => "error",
+            _ => "warning"
+        }
+    }
+}
+
+/// A suspicious character, at a 1-based line and column counted in characters
+#[derive(Debug, PartialEq)]
+pub struct Finding {
+    pub line: usize,
+    pub col: usize,
+    pub kind: Kind,
+    pub cp: u32,
+    pub message: String
+}
+
+pub struct Linter {
+    search: Search,
+    confusables: Confusables,
+    chars: HashMap<char, Option<CharInfo>>,
+    lookalikes: HashMap<char, Option<String>>
+}
+
+impl Linter {
+    pub fn new(search: Search, confusables: Confusables) -> Linter {
+        Linter { search, confusables, chars: HashMap::new(), lookalikes: HashMap::new() }
+    }
+
+    fn char_info(&mut self, c: char) -> Option<CharInfo> {
+        let search = &self.search;
+        self.chars.entry(c).or_insert_with(|| search.lookup(c as u32).ok().and_then(|i| i)).clone()
+    }
+
+    /// The name of `c` for messages, looked up in the index
+    fn describe(&mut self, c: char) -> String {
+        match self.char_info(c) {
+            Some(info) => info.describe(),
+            None => format!("U+{:04X}", c as u32)
+        }
+    }
+
+    /// The ASCII text a non-ASCII `c` is drawn like, if its UTS #39 skeleton is one
+    fn lookalike(&mut self, c: char) -> Option<String> {
+        if c.is_ascii() { return None; }
+        let (search, confusables) = (&self.search, &self.confusables);
+        self.lookalikes.entry(c).or_insert_with(|| {
+            let skeleton = confusables.skeleton(&c.to_string(), &mut Normalizer::new(search)).ok()?;
+            Some(skeleton).filter(|s| s.is_ascii())
+        }).clone()
+    }
+
+    fn script(&mut self, c: char) -> Option<String> {
+        if c.is_ascii_alphabetic() { return Some(String::from("Latn")); }
+        if c.is_ascii() { return None; }
+        self.char_info(c).map(|i| i.script).filter(|sc| !matches!(sc.as_str(), "Zyyy" | "Zinh" | "Zzzz"))
+    }
+
+    /// Check a single character on its own
+    fn check_char(&mut self, c: char) -> Option<(Kind, String)> {
+        if c.is_ascii() && (c.is_ascii_graphic() || c == ' ' || c == '\t' || c == '\n' || c == '\r') {
+            return None;
+        }
+        match self.char_info(c) {
+            Some(ref info) if info.is_bidi_control() => Some((Kind::Bidi, format!("bidirectional control character {}", info.describe()))),
+            Some(ref info) if info.is_invisible() => Some((Kind::Invisible, format!("invisible character {}", info.describe()))),
+            Some(ref info) if info.category == "Co" => Some((Kind::PrivateUse, format!("private use character {}", info.describe()))),
+            Some(ref info) if info.category == "Cn" => Some((Kind::Unassigned, format!("unassigned codepoint {}", info.describe()))),
+            Some(_) => None,
+            None if is_private_use(c as u32) => Some((Kind::PrivateUse, format!("private use character U+{:04X}", c as u32))),
+            // ideographs and Hangul syllables aren't in the index one by one, but everything else assigned is
+            None if !c.is_alphabetic() => Some((Kind::Unassigned, format!("unassigned codepoint U+{:04X}", c as u32))),
+            None => None
+        }
+    }
+
+    /// Check the letters of one identifier-like word, which starts at `col`
+    fn check_word(&mut self, word: &[char], line: usize, col: usize, found: &mut Vec<Finding>) {
+        if word.iter().all(char::is_ascii) { return; }
+        let mut flagged = false;
+        if word.iter().any(char::is_ascii_alphabetic) {
+            for (i, &c) in word.iter().enumerate() {
+                if let Some(a) = self.lookalike(c) {
+                    let message = format!("{} looks like '{}'", self.describe(c), a);
+                    found.push(Finding { line, col: col + i, kind: Kind::Lookalike, cp: c as u32, message });
+                    flagged = true;
+                }
+            }
+        }
+        if flagged { return; }
+        let mut first: Option<String> = None;
+        for (i, &c) in word.iter().enumerate() {
+            let sc = match self.script(c) { Some(sc) => sc, None => continue };
+            match first {
+                None => first = Some(sc),
+                Some(ref f) if script_group(f) != script_group(&sc) => {
+                    let message = format!("{} in {} is {}, not {}", self.describe(c), word.iter().collect::<String>(), sc, f);
+                    found.push(Finding { line, col: col + i, kind: Kind::MixedScript, cp: c as u32, message });
+                    return;
+                },
+                _ => {}
+            }
+        }
+    }
+
+    /// Every suspicious character in `text`, in order
+    pub fn check(&mut self, text: &str) -> Vec<Finding> {
+        // a byte order mark at the very start is expected
+        let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+        let mut found = Vec::new();
+        for (l, line) in text.split('\n').enumerate() {
+            let mut word = Vec::new();
+            for (i, c) in line.chars().chain(Some('\n')).enumerate() {
+                if c.is_alphanumeric() || c == '_' {
+                    word.push(c);
+                } else if !word.is_empty() {
+                    self.check_word(&word, l + 1, i + 1 - word.len(), &mut found);
+                    word.clear();
+                }
+                if c != '\n' {
+                    if let Some((kind, message)) = self.check_char(c) {
+                        found.push(Finding { line: l + 1, col: i + 1, kind, cp: c as u32, message });
+                    }
+                }
+            }
+        }
+        found.sort_by_key(|f| (f.line, f.col));
+        found
+    }
+}
+
+fn format_text(path: &str, f: &Finding) -> String {
+    format!("{}:{}:{}: {}[{}]: {}", path, f.line, f.col, f.kind.severity(), f.kind.id(), f.message)
+}
+
+fn format_json(path: &str, f: &Finding) -> String {
+    json!({
+        "path": path,
+        "line": f.line,
+        "column": f.col,
+        "severity": f.kind.severity(),
+        "kind": f.kind.id(),
+        "cp": f.cp,
+        "codepoint": format!("U+{:04X}", f.cp),
+        "message": f.message
+    }).to_string()
+}
+
+/// `ununi lint [--format text|json] [FILE...]` reports suspicious characters in files, or stdin when there are none.
+/// The status to exit with is 0 when nothing was found, 1 when something was and 2 when a file couldn't be read.
+pub fn run(config: &Option<TomlValue>, args: &[String]) -> Result<i32, Box<dyn Error>> {
+    let mut format: fn(&str, &Finding) -> String = format_text;
+    let mut paths = Vec::new();
+    let mut args = args.iter();
+    while let Some(a) = args.next() {
+        match a.as_str() {
+            "--format" => format = match args.next().map(String::as_str) {
+                Some("text") => format_text,
+                Some("json") => format_json,
+                _ => return Err("--format needs one of: text, json".into())
+            },
+            _ if a.starts_with("--") => return Err(format!("unknown argument {}; usage: ununi lint [--format text|json] [FILE...]", a).into()),
+            _ => paths.push(a.clone())
+        }
+    }
+    if paths.is_empty() {
+        paths.push(String::from("-"));
+    }
+
+    let mut linter = Linter::new(Search::open(config)?, Confusables::open()?);
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let mut status = 0;
+    for path in &paths {
+        let bytes = if path == "-" {
+            let mut b = Vec::new();
+            io::stdin().read_to_end(&mut b).map(|_| b)
+        } else {
+            fs::read(path)
+        };
+        let text = match bytes.map(String::from_utf8) {
+            Ok(Ok(text)) => text,
+            // binary files have nothing for us to say about them
+            Ok(Err(_)) => { eprintln!("{}: skipped, not UTF-8", path); continue; },
+            Err(e) => { eprintln!("{}: {}", path, e); status = 2; continue; }
+        };
+        let name = if path == "-" { "<stdin>" } else { path.as_str() };
+        for f in linter.check(&text) {
+            writeln!(out, "{}", format(name, &f))?;
+            if status == 0 { status = 1; }
+        }
+    }
+    out.flush()?;
+    Ok(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use ::lint::*;
+    use ::search::tests::fixture;
+
+    const CHARS: &str = r#"<group blk="Cyrillic" gc="Ll" sc="Cyrl" bc="L">
+<char cp="0430" na="CYRILLIC SMALL LETTER A"/>
+</group>
+<group blk="Punctuation" gc="Cf" sc="Zyyy">
+<char cp="200B" na="ZERO WIDTH SPACE" bc="BN"><name-alias alias="ZWSP" type="abbreviation"/></char>
+<char cp="202E" na="RIGHT-TO-LEFT OVERRIDE" bc="RLO"><name-alias alias="RLO" type="abbreviation"/></char>
+</group>
+"#;
+
+    const CONFUSABLES: &str = "0430 ;\t0061 ;\tMA\t# ( а → a ) CYRILLIC SMALL LETTER A → LATIN SMALL LETTER A
+FF41 ;\t0061 ;\tMA\t# ( ａ → a ) FULLWIDTH LATIN SMALL LETTER A → LATIN SMALL LETTER A
+";
+
+    fn linter() -> Linter {
+        Linter::new(fixture(CHARS), Confusables::parse(CONFUSABLES.as_bytes(), &b""[..]).unwrap())
+    }
+
+    fn kinds(found: &[Finding]) -> Vec<(usize, usize, Kind)> {
+        found.iter().map(|f| (f.line, f.col, f.kind)).collect()
+    }
+
+    #[test]
+    fn finds_suspicious_characters() {
+        let mut linter = linter();
+        let found = linter.check("\u{feff}if admin\u{202e} {\n  x\u{200b} = '\u{e000}';\n}\n");
+        assert_eq!(kinds(&found), vec![(1, 9, Kind::Bidi), (2, 4, Kind::Invisible), (2, 9, Kind::PrivateUse)]);
+        assert_eq!(found[0].message, "bidirectional control character U+202E RIGHT-TO-LEFT OVERRIDE (RLO)");
+        assert_eq!(format_text("a.rs", &found[1]), "a.rs:2:4: warning[invisible]: invisible character U+200B ZERO WIDTH SPACE (ZWSP)");
+        // not in the fixture and not a letter
+        assert_eq!(kinds(&linter.check("\u{2191}")), vec![(1, 1, Kind::Unassigned)]);
+        assert!(linter.check("λ → x\tok\r\n").is_empty());
+    }
+
+    #[test]
+    fn identifiers() {
+        let mut linter = linter();
+        let found = linter.check("let p\u{430}ss = λx;");
+        assert_eq!(kinds(&found), vec![(1, 6, Kind::Lookalike), (1, 13, Kind::MixedScript)]);
+        assert_eq!(found[0].message, "U+0430 CYRILLIC SMALL LETTER A looks like 'a'");
+        assert_eq!(found[1].message, "U+0078 in λx is Latn, not Grek");
+        // a whole word in another script is fine
+        assert!(linter.check("λ \u{430}").is_empty());
+        assert_eq!(kinds(&linter.check("p\u{ff41}ss")), vec![(1, 2, Kind::Lookalike)]);
+        let json: ::serde_json::Value = ::serde_json::from_str(&format_json("-", &found[0])).unwrap();
+        assert_eq!(json["kind"], "lookalike");
+        assert_eq!(json["column"], 6);
+    }
+}
@@ -0,0 +1,452 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+use serde_json::Value as JsonValue;
+use toml::Value as TomlValue;
+
+use search::{Search, CharInfo, QError, literal_query};
+
+/// Characters that start a completion, ex. `\rightarrow` or `:alpha`
+const TRIGGERS: [char; 2] = ['\\', ':'];
+const COMPLETION_ITEMS: usize = 50;
+
+/// Read one `Content-Length` framed message, or None at the end of the stream
+pub fn read_message<R: BufRead>(r: &mut R) -> Result<Option<JsonValue>, Box<dyn Error>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if r.read_line(&mut header)? == 0 { return Ok(None); }
+        let header = header.trim();
+        if header.is_empty() { break; }
+        let mut kv = header.splitn(2, ':');
+        if kv.next().is_some_and(|k| k.trim().eq_ignore_ascii_case("Content-Length")) {
+            length = Some(kv.next().unwrap_or("").trim().parse::<usize>()?);
+        }
+    }
+    let mut body = vec![0u8; length.ok_or("message without a Content-Length header")?];
+    r.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+pub fn write_message<W: Write>(w: &mut W, msg: &JsonValue) -> io::Result<()> {
+    let body = msg.to_string();
+    write!(w, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    w.flush()
+}
+
+/// A request without a parameter it needs, answered with InvalidParams rather than InternalError
+#[derive(Debug)]
+struct InvalidParams(&'static str);
+
+impl Error for InvalidParams {}
+
+impl fmt::Display for InvalidParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+/// Convert an LSP position (a line and a UTF-16 offset into it) to a byte offset into `text`
+fn byte_offset(text: &str, line: usize, character: usize) -> usize {
+    let line_start = if line == 0 { 0 } else {
+        match text.match_indices('\n').nth(line - 1) {
+            Some((i, _)) => i + 1,
+            None => return text.len()
+        }
+    };
+    let mut units = 0;
+    for (i, c) in text[line_start..].char_indices() {
+        if units >= character || c == '\n' { return line_start + i; }
+        units += c.len_utf16();
+    }
+    text.len()
+}
+
+/// The trigger character and the word typed after it that ends at `offset`, if there is one
+fn completion_word(text: &str, offset: usize) -> Option<(usize, &str)> {
+    let before = &text[..offset];
+    let start = before.rfind(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-')).unwrap_or(0);
+    let trigger = before[start..].chars().next()?;
+    if TRIGGERS.contains(&trigger) { Some((start, &before[start + 1..])) } else { None }
+}
+
+fn utf16_len(s: &str) -> usize {
+    s.chars().map(char::len_utf16).sum()
+}
+
+fn needs_lookup(c: char) -> bool {
+    !(c.is_ascii_graphic() || c == ' ' || c == '\t' || c == '\n' || c == '\r')
+}
+
+/// An invisible or unusual character found in a document
+struct Unusual {
+    line: usize,
+    character: usize, // in UTF-16 code units
+    info: CharInfo
+}
+
+impl Unusual {
+    fn range(&self) -> JsonValue {
+        let len = self.info.chr().map_or(1, char::len_utf16);
+        json!({ "start": { "line": self.line, "character": self.character },
+                "end": { "line": self.line, "character": self.character + len } })
+    }
+}
+
+/// Answers completion requests from an editor on stdin/stdout
+pub struct LanguageServer {
+    search: Search,
+    documents: HashMap<String, String>,
+    chars: HashMap<char, Option<CharInfo>>,
+    shutdown: bool
+}
+
+impl LanguageServer {
+    pub fn new(search: Search) -> LanguageServer {
+        LanguageServer { search, documents: HashMap::new(), chars: HashMap::new(), shutdown: false }
+    }
+
+    fn char_info(&mut self, c: char) -> Option<CharInfo> {
+        let search = &self.search;
+        self.chars.entry(c).or_insert_with(|| search.lookup(c as u32).ok().and_then(|i| i)).clone()
+    }
+
+    fn is_pictographic(&mut self, c: char) -> bool {
+        needs_lookup(c) && self.char_info(c).is_some_and(|i| i.extended_pictographic)
+    }
+
+    /// Every invisible character in `text`, except zero width joiners that join pictographs into one emoji
+    fn unusual_chars(&mut self, text: &str) -> Vec<Unusual> {
+        let mut found = Vec::new();
+        for (line, l) in text.split('\n').enumerate() {
+            let mut character = 0;
+            let mut pict = false; // after an extended pictographic character and its extenders
+            let mut chars = l.chars().peekable();
+            while let Some(c) = chars.next() {
+                let info = if needs_lookup(c) { self.char_info(c) } else { None };
+                let joins = c == '\u{200d}' && pict && chars.peek().is_some_and(|&n| self.is_pictographic(n));
+                if let Some(info) = info.clone().filter(|i| !joins && i.is_invisible()) {
+                    found.push(Unusual { line, character, info });
+                }
+                pict = info.as_ref().is_some_and(|i| i.extended_pictographic || (pict && i.grapheme_break == "EX"));
+                character += c.len_utf16();
+            }
+        }
+        found
+    }
+
+    fn diagnostics(&mut self, uri: &str) -> JsonValue {
+        let text = self.documents.get(uri).cloned().unwrap_or_default();
+        let diagnostics = self.unusual_chars(&text).iter().map(|u| json!({
+            "range": u.range(),
+            // bidi controls can make code read differently from how it runs
+            "severity": if u.info.is_bidi_control() { 1 } else { 2 },
+            "source": "ununi",
+            "message": if u.info.is_bidi_control() { format!("bidirectional control character {}", u.info.describe()) }
+                       else { format!("invisible character {}", u.info.describe()) }
+        })).collect::<Vec<_>>();
+        json!({ "jsonrpc": "2.0", "method": "textDocument/publishDiagnostics",
+                "params": { "uri": uri, "diagnostics": diagnostics } })
+    }
+
+    fn hover(&mut self, params: &JsonValue) -> Result<JsonValue, Box<dyn Error>> {
+        let uri = params["textDocument"]["uri"].as_str().ok_or(InvalidParams("missing textDocument.uri"))?;
+        let line = params["position"]["line"].as_u64().ok_or(InvalidParams("missing position.line"))? as usize;
+        let character = params["position"]["character"].as_u64().ok_or(InvalidParams("missing position.character"))? as usize;
+        let c = match self.documents.get(uri) {
+            Some(text) => match text[byte_offset(text, line, character)..].chars().next() {
+                Some(c) if c != '\n' => c,
+                _ => return Ok(JsonValue::Null)
+            },
+            None => return Ok(JsonValue::Null)
+        };
+        if !needs_lookup(c) {
+            return Ok(JsonValue::Null);
+        }
+        let info = match self.char_info(c) {
+            Some(info) => info,
+            None => return Ok(JsonValue::Null)
+        };
+        let mut contents = format!("**{}**", info.name);
+        if !info.aliases.is_empty() {
+            contents += &format!(" ({})", info.aliases.join(", "));
+        }
+        contents += &format!("\n\nU+{:04X} · {} · {}", info.cp, info.block, info.category);
+        Ok(json!({ "contents": { "kind": "markdown", "value": contents } }))
+    }
+
+    fn inlay_hints(&mut self, params: &JsonValue) -> Result<JsonValue, Box<dyn Error>> {
+        let uri = params["textDocument"]["uri"].as_str().ok_or(InvalidParams("missing textDocument.uri"))?;
+        let first = params["range"]["start"]["line"].as_u64().unwrap_or(0) as usize;
+        let last = params["range"]["end"]["line"].as_u64().map_or(usize::MAX, |l| l as usize);
+        let text = match self.documents.get(uri) {
+            Some(text) => text.clone(),
+            None => return Ok(JsonValue::Null)
+        };
+        Ok(JsonValue::Array(self.unusual_chars(&text).iter()
+            .filter(|u| u.line >= first && u.line <= last)
+            .map(|u| json!({
+                "position": { "line": u.line, "character": u.character },
+                "label": format!("\u{27e8}{}\u{27e9}", u.info.short_name()),
+                "tooltip": u.info.describe()
+            })).collect()))
+    }
+
+    fn completion(&mut self, params: &JsonValue) -> Result<JsonValue, Box<dyn Error>> {
+        let uri = params["textDocument"]["uri"].as_str().ok_or(InvalidParams("missing textDocument.uri"))?;
+        let text = match self.documents.get(uri) {
+            Some(t) => t,
+            None => return Ok(JsonValue::Null)
+        };
+        let line = params["position"]["line"].as_u64().ok_or(InvalidParams("missing position.line"))? as usize;
+        let character = params["position"]["character"].as_u64().ok_or(InvalidParams("missing position.character"))? as usize;
+        let offset = byte_offset(text, line, character);
+        let (start, word) = match completion_word(text, offset) {
+            Some(w) => w,
+            None => return Ok(json!({ "isIncomplete": false, "items": [] }))
+        };
+        let query = word.replace(['_', '-'], " ");
+        if query.trim().is_empty() {
+            return Ok(json!({ "isIncomplete": true, "items": [] }));
+        }
+        // replace the trigger and the word with the character. A position in the middle of a surrogate pair
+        // ends the word after the pair, so the word can be longer than the position.
+        let start_col = character.saturating_sub(utf16_len(&text[start..offset]));
+        let range = json!({ "start": { "line": line, "character": start_col }, "end": { "line": line, "character": character } });
+        let typed = &text[start..offset];
+        // a word like `AND` means something to the query language, but here it is just a word
+        let results = match self.search.query(&query, COMPLETION_ITEMS) {
+            Err(ref e) if e.is::<QError>() => self.search.query(&literal_query(&query), COMPLETION_ITEMS)?,
+            results => results?
+        };
+        let items = results.iter().enumerate()
+            .filter_map(|(i, c)| c.chr().map(|ch| json!({
+                "label": format!("{} {}", ch, c.name),
+                "kind": 21, // Constant
+                "detail": format!("U+{:04X} {}", c.cp, c.block),
+                // keep the editor from filtering out results we ranked ourselves
+                "filterText": typed,
+                "sortText": format!("{:04}", i),
+                "textEdit": { "range": range, "newText": ch.to_string() }
+            })))
+            .collect::<Vec<_>>();
+        Ok(json!({ "isIncomplete": true, "items": items }))
+    }
+
+    /// Answer a request, or None when the method isn't one we know
+    fn request(&mut self, method: &str, params: &JsonValue) -> Option<Result<JsonValue, Box<dyn Error>>> {
+        Some(match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": 1, // Full
+                    "completionProvider": { "triggerCharacters": TRIGGERS.iter().map(|c| c.to_string()).collect::<Vec<_>>() },
+                    "hoverProvider": true,
+                    "inlayHintProvider": true
+                },
+                "serverInfo": { "name": "ununi", "version": env!("CARGO_PKG_VERSION") }
+            })),
+            "shutdown" => { self.shutdown = true; Ok(JsonValue::Null) },
+            "textDocument/completion" => self.completion(params),
+            "textDocument/hover" => self.hover(params),
+            "textDocument/inlayHint" => self.inlay_hints(params),
+            _ => return None
+        })
+    }
+
+    /// Handle a notification, returning any notifications to send back
+    fn notification(&mut self, method: &str, params: &JsonValue) -> Vec<JsonValue> {
+        match method {
+            "textDocument/didOpen" => {
+                if let (Some(uri), Some(text)) = (params["textDocument"]["uri"].as_str(), params["textDocument"]["text"].as_str()) {
+                    self.documents.insert(uri.to_owned(), text.to_owned());
+                    return vec![self.diagnostics(uri)];
+                }
+            },
+            "textDocument/didChange" => {
+                let text = params["contentChanges"].as_array().and_then(|ch| ch.last()).and_then(|ch| ch["text"].as_str());
+                if let (Some(uri), Some(text)) = (params["textDocument"]["uri"].as_str(), text) {
+                    self.documents.insert(uri.to_owned(), text.to_owned());
+                    return vec![self.diagnostics(uri)];
+                }
+            },
+            "textDocument/didClose" => {
+                if let Some(uri) = params["textDocument"]["uri"].as_str() {
+                    self.documents.remove(uri);
+                }
+            },
+            _ => {}
+        }
+        Vec::new()
+    }
+
+    /// Handle one message from the client, returning the messages to send back: the response to
+    /// a request, or diagnostics after a document changed.
+    /// The `exit` notification returns Err with the process exit code.
+    pub fn handle(&mut self, msg: &JsonValue) -> Result<Vec<JsonValue>, i32> {
+        let method = msg["method"].as_str().unwrap_or("");
+        let params = &msg["params"];
+        if method == "exit" {
+            return Err(if self.shutdown { 0 } else { 1 });
+        }
+        match msg.get("id") {
+            Some(id) => Ok(vec![match self.request(method, params) {
+                Some(Ok(result)) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                Some(Err(e)) => {
+                    let code = if e.is::<InvalidParams>() { -32602 } else { -32603 };
+                    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": e.to_string() } })
+                },
+                None => json!({ "jsonrpc": "2.0", "id": id, "error": { "code": -32601, "message": format!("unsupported method {}", method) } })
+            }]),
+            None => Ok(self.notification(method, params))
+        }
+    }
+}
+
+/// `ununi lsp` speaks the Language Server Protocol on stdin/stdout, until it is told to exit with a status
+pub fn run(config: &Option<TomlValue>) -> Result<i32, Box<dyn Error>> {
+    let mut server = LanguageServer::new(Search::open(config)?);
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    let stdout = io::stdout();
+    let mut output = stdout.lock();
+    while let Some(msg) = read_message(&mut input)? {
+        match server.handle(&msg) {
+            Ok(msgs) => for m in msgs { write_message(&mut output, &m)?; },
+            Err(code) => return Ok(code)
+        }
+    }
+    Ok(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use ::lsp::*;
+    use ::search::tests::fixture;
+
+    const CHARS: &str = r#"<group blk="Latin_1_Sup" gc="Zs" sc="Zyyy" bc="CS">
+<char cp="00A0" na="NO-BREAK SPACE"><name-alias alias="NBSP" type="abbreviation"/></char>
+</group>
+<group blk="Punctuation" gc="Cf" sc="Zyyy">
+<char cp="200B" na="ZERO WIDTH SPACE" bc="BN"><name-alias alias="ZWSP" type="abbreviation"/></char>
+<char cp="200D" na="ZERO WIDTH JOINER" bc="BN" GCB="ZWJ"><name-alias alias="ZWJ" type="abbreviation"/></char>
+<char cp="202E" na="RIGHT-TO-LEFT OVERRIDE" bc="RLO"><name-alias alias="RLO" type="abbreviation"/></char>
+</group>
+<group blk="Misc_Pictographs" gc="So" sc="Zyyy" bc="ON" ExtPict="Y">
+<char cp="1F469" na="WOMAN"/>
+<char cp="1F4BB" na="PERSONAL COMPUTER"/>
+</group>
+"#;
+
+    fn open(server: &mut LanguageServer, text: &str) -> Vec<JsonValue> {
+        server.handle(&json!({ "jsonrpc": "2.0", "method": "textDocument/didOpen",
+            "params": { "textDocument": { "uri": "file:///a.txt", "languageId": "plaintext", "version": 1, "text": text } } })).unwrap()
+    }
+
+    fn request(server: &mut LanguageServer, method: &str, params: JsonValue) -> JsonValue {
+        server.handle(&json!({ "jsonrpc": "2.0", "id": 2, "method": method, "params": params })).unwrap()[0]["result"].clone()
+    }
+
+    fn complete(server: &mut LanguageServer, line: usize, character: usize) -> JsonValue {
+        request(server, "textDocument/completion",
+                json!({ "textDocument": { "uri": "file:///a.txt" }, "position": { "line": line, "character": character } }))
+    }
+
+    #[test]
+    fn completes_after_a_trigger() {
+        let mut server = LanguageServer::new(fixture(CHARS));
+        open(&mut server, "x → y\nlet f = \\lamda");
+        let res = complete(&mut server, 1, 14);
+        assert_eq!(res["items"][0]["textEdit"]["newText"], "λ");
+        assert_eq!(res["items"][0]["textEdit"]["range"], json!({ "start": { "line": 1, "character": 8 }, "end": { "line": 1, "character": 14 } }));
+        assert_eq!(res["items"][0]["filterText"], "\\lamda");
+
+        open(&mut server, "→ :rightwards_arrow");
+        let res = complete(&mut server, 0, 19);
+        assert_eq!(res["items"][0]["textEdit"]["newText"], "→");
+        assert_eq!(res["items"][0]["textEdit"]["range"]["start"]["character"], 2);
+
+        // words that are operators in the query language are searched for as words
+        open(&mut server, ":leftwards_arrow_OR");
+        assert_eq!(complete(&mut server, 0, 19)["items"][0]["textEdit"]["newText"], "←");
+
+        open(&mut server, "no trigger");
+        assert_eq!(complete(&mut server, 0, 10)["items"], json!([]));
+
+        open(&mut server, "\\\u{1d400}");
+        assert!(complete(&mut server, 0, 2)["items"].is_array());
+    }
+
+    #[test]
+    fn lifecycle() {
+        let mut server = LanguageServer::new(fixture(CHARS));
+        let init = server.handle(&json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {} })).unwrap();
+        assert_eq!(init[0]["result"]["capabilities"]["completionProvider"]["triggerCharacters"], json!(["\\", ":"]));
+        let unknown = server.handle(&json!({ "jsonrpc": "2.0", "id": 3, "method": "textDocument/codeLens", "params": {} })).unwrap();
+        assert_eq!(unknown[0]["error"]["code"], -32601);
+        let hover = server.handle(&json!({ "jsonrpc": "2.0", "id": 4, "method": "textDocument/hover",
+                                           "params": { "textDocument": { "uri": "file:///a.txt" } } })).unwrap();
+        assert_eq!((hover[0]["error"]["code"].as_i64(), hover[0]["error"]["message"].as_str()), (Some(-32602), Some("missing position.line")));
+        assert_eq!(server.handle(&json!({ "jsonrpc": "2.0", "method": "exit" })), Err(1));
+        server.handle(&json!({ "jsonrpc": "2.0", "id": 2, "method": "shutdown" })).unwrap();
+        assert_eq!(server.handle(&json!({ "jsonrpc": "2.0", "method": "exit" })), Err(0));
+    }
+
+    #[test]
+    fn invisible_characters() {
+        let mut server = LanguageServer::new(fixture(CHARS));
+        let msgs = open(&mut server, "let a\u{a0}= 1;\n// x\u{202e}y\u{200b}");
+        assert_eq!(msgs[0]["method"], "textDocument/publishDiagnostics");
+        let diags = msgs[0]["params"]["diagnostics"].as_array().unwrap();
+        assert_eq!(diags.len(), 3);
+        assert_eq!(diags[0]["message"], "invisible character U+00A0 NO-BREAK SPACE (NBSP)");
+        assert_eq!(diags[0]["severity"], 2);
+        assert_eq!(diags[1]["severity"], 1);
+        assert_eq!(diags[1]["range"], json!({ "start": { "line": 1, "character": 4 }, "end": { "line": 1, "character": 5 } }));
+
+        let hints = request(&mut server, "textDocument/inlayHint", json!({ "textDocument": { "uri": "file:///a.txt" },
+            "range": { "start": { "line": 1, "character": 0 }, "end": { "line": 1, "character": 99 } } }));
+        assert_eq!(hints.as_array().unwrap().len(), 2);
+        assert_eq!(hints[1]["label"], "\u{27e8}ZWSP\u{27e9}");
+        assert_eq!(hints[1]["position"], json!({ "line": 1, "character": 6 }));
+
+        let hover = request(&mut server, "textDocument/hover",
+                            json!({ "textDocument": { "uri": "file:///a.txt" }, "position": { "line": 0, "character": 5 } }));
+        assert_eq!(hover["contents"]["value"], "**NO-BREAK SPACE** (NBSP)\n\nU+00A0 · Latin_1_Sup · Zs");
+        let hover = request(&mut server, "textDocument/hover",
+                            json!({ "textDocument": { "uri": "file:///a.txt" }, "position": { "line": 0, "character": 0 } }));
+        assert_eq!(hover, JsonValue::Null);
+    }
+
+    #[test]
+    fn emoji_joiners() {
+        let mut server = LanguageServer::new(fixture(CHARS));
+        // a woman technologist is one emoji, a joiner between letters or at the end of one is not
+        let msgs = open(&mut server, "\u{1f469}\u{200d}\u{1f4bb}\na\u{200d}b \u{1f469}\u{200d}");
+        let diags = msgs[0]["params"]["diagnostics"].as_array().unwrap();
+        assert_eq!(diags.len(), 2);
+        assert_eq!(diags[0]["range"]["start"], json!({ "line": 1, "character": 1 }));
+        assert_eq!(diags[1]["range"]["start"], json!({ "line": 1, "character": 6 }));
+    }
+
+    #[test]
+    fn framing() {
+        let mut buf = Vec::new();
+        write_message(&mut buf, &json!({ "jsonrpc": "2.0", "id": 1, "result": "λ" })).unwrap();
+        write_message(&mut buf, &json!({ "jsonrpc": "2.0", "method": "exit" })).unwrap();
+        let mut r = io::Cursor::new(buf);
+        assert_eq!(read_message(&mut r).unwrap().unwrap()["result"], "λ");
+        assert_eq!(read_message(&mut r).unwrap().unwrap()["method"], "exit");
+        assert!(read_message(&mut r).unwrap().is_none());
+    }
+
+    #[test]
+    fn positions() {
+        let text = "a😀b\nλx";
+        assert_eq!(byte_offset(text, 0, 3), 5);
+        assert_eq!(byte_offset(text, 1, 1), 9);
+        assert_eq!(byte_offset(text, 0, 99), 6);
+        assert_eq!(byte_offset(text, 5, 0), text.len());
+    }
+}
@@ -1,43 +1,198 @@
-#![windows_subsystem = "windows"]
+#![cfg_attr(windows, windows_subsystem = "windows")]
 extern crate tantivy;
+extern crate levenshtein_automata;
 extern crate xml;
+#[cfg(windows)]
 extern crate winapi;
 extern crate curl;
 extern crate zip;
 extern crate toml;
+#[macro_use]
+extern crate serde_json;
 
+mod search;
+#[cfg(any(windows, test))]
+mod editor;
+mod dmenu;
+mod history;
+mod daemon;
+mod serve;
+mod lsp;
+mod lint;
+mod confusables;
+mod describe;
+mod normalize;
+mod styles;
+mod compose;
+mod formats;
+mod detail;
+mod browse;
+mod grid;
+mod emoji;
+mod rank;
+mod fuzzy;
+mod quality;
+mod suggest;
+mod answer;
+#[cfg(windows)]
 mod vgu;
+#[cfg(windows)]
 mod app;
 
+#[cfg(windows)]
 use winapi::shared::minwindef::*;
+#[cfg(windows)]
 use winapi::um::winuser::*;
+#[cfg(windows)]
 use winapi::um::winnt::*;
+#[cfg(windows)]
 use winapi::um::winreg::*;
+#[cfg(windows)]
 use winapi::um::libloaderapi::GetModuleFileNameW;
+#[cfg(windows)]
 use std::ptr::{null_mut};
+#[cfg(windows)]
 use std::mem::{MaybeUninit, transmute};
 use std::io::{Read, ErrorKind as IOErrorKind};
+use std::path::{Path, PathBuf};
+use std::error::Error;
 use std::env;
 use toml::Value as TomlValue;
 
-fn display_error<E: ::std::error::Error>(e: &E) {
+#[cfg(windows)]
+fn display_error<E: Error + ?Sized>(e: &E) {
     let mut text = format!("Error: {}", e).encode_utf16().collect::<Vec<u16>>();
     text.push(0); text.push(0);
     unsafe { MessageBoxW(null_mut(), text.as_ptr(), null_mut(), MB_ICONERROR) };
 }
 
+#[cfg(not(windows))]
+fn display_error<E: Error + ?Sized>(e: &E) {
+    eprintln!("Error: {}", e);
+}
+
+/// The directory ununi keeps its index and downloaded data in
+#[cfg(windows)]
+fn data_dir() -> PathBuf {
+    Path::new(&env::var("APPDATA").expect("%APPDATA% env var")).join("ununi")
+}
+
+#[cfg(not(windows))]
+fn data_dir() -> PathBuf {
+    env::var_os("XDG_DATA_HOME").map(PathBuf::from)
+        .unwrap_or_else(|| Path::new(&env::var("HOME").expect("$HOME env var")).join(".local/share"))
+        .join("ununi")
+}
+
+/// Where config.toml lives; on Windows this is the same as the data directory
+#[cfg(windows)]
+fn config_dir() -> PathBuf {
+    data_dir()
+}
+
+#[cfg(not(windows))]
+fn config_dir() -> PathBuf {
+    env::var_os("XDG_CONFIG_HOME").map(PathBuf::from)
+        .unwrap_or_else(|| Path::new(&env::var("HOME").expect("$HOME env var")).join(".config"))
+        .join("ununi")
+}
+
+fn load_config() -> Result<Option<TomlValue>, Box<dyn Error>> {
+    match ::std::fs::File::open(config_dir().join("config.toml")) {
+        Ok(mut f) => {
+            let mut config_text = String::new();
+            f.read_to_string(&mut config_text)?;
+            Ok(Some(config_text.parse::<TomlValue>()?))
+        },
+        Err(e) => match e.kind() {
+            IOErrorKind::NotFound => Ok(None),
+            _ => Err(Box::new(e))
+        }
+    }
+}
+
+/// Run one of the subcommands that work the same on every platform, if one was given, returning the status to exit with
+fn run_command(config: &Option<TomlValue>, args: &[String]) -> Option<Result<i32, Box<dyn Error>>> {
+    let status = |r: Result<(), Box<dyn Error>>| r.map(|()| 0);
+    match args.first().map(String::as_str) {
+        Some("dmenu") => Some(status(dmenu::run(config, &args[1..]))),
+        Some("daemon") => Some(status(daemon::run(config, &args[1..]))),
+        Some("serve") => Some(status(serve::run(config, &args[1..]))),
+        Some("lsp") => Some(lsp::run(config)),
+        Some("lint") => Some(lint::run(config, &args[1..])),
+        Some("skeleton") => Some(confusables::run_skeleton(config, &args[1..])),
+        Some("confusables") => Some(status(confusables::run_confusables(config, &args[1..]))),
+        Some("describe") => Some(status(describe::run(config, &args[1..]))),
+        Some("normalize") => Some(status(normalize::run(config, &args[1..]))),
+        Some("style") => Some(status(styles::run(config, &args[1..]))),
+        Some("compose") => Some(status(compose::run(config, &args[1..]))),
+        Some("info") => Some(status(detail::run(config, &args[1..]))),
+        Some("browse") => Some(status(browse::run(config, &args[1..]))),
+        Some("grid") => Some(status(grid::run(config, &args[1..]))),
+        Some("emoji") => Some(status(emoji::run(&args[1..]))),
+        Some("quality") => Some(status(quality::run(config, &args[1..]))),
+        Some("suggest") => Some(status(suggest::run(config, &args[1..]))),
+        _ => None
+    }
+}
+
+#[cfg(not(windows))]
+fn main() {
+    // check to see if the data directory exists, if not create it, then change directories there
+    let data_path = data_dir();
+    if !data_path.exists() {
+        ::std::fs::create_dir_all(&data_path).expect("create ununi data directory");
+    }
+    env::set_current_dir(data_path).expect("change directory into ununi data directory");
+
+    let config = match load_config() {
+        Ok(c) => c,
+        Err(e) => { display_error(&*e); ::std::process::exit(1); }
+    };
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let usage = || Err("usage: ununi COMMAND, where COMMAND is one of dmenu, daemon, serve, lsp, lint, skeleton, confusables, \
+                        describe, normalize, style, compose, info, browse, grid, emoji, quality or suggest".into());
+    match run_command(&config, &args).unwrap_or_else(usage) {
+        Ok(0) => {},
+        Ok(status) => ::std::process::exit(status),
+        Err(e) => {
+            display_error(&*e);
+            ::std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(windows)]
 fn main() {
     unsafe {
         vgu::SetProcessDpiAwareness(1);
     }
 
     // check to see if %APPDATA% directory exists, if not create it, then change directories there
-    let appdata_path = ::std::path::Path::new(&env::var("APPDATA").expect("%APPDATA% env var")).join("ununi");
+    let appdata_path = data_dir();
     if !appdata_path.exists() {
         ::std::fs::create_dir_all(&appdata_path).expect("create %APPDATA%\\ununi directory");
     }
     env::set_current_dir(appdata_path).expect("change directory into %APPDATA%\\ununi");
 
+    let config = match load_config() {
+        Ok(c) => c,
+        Err(e) => {
+            display_error(&*e);
+            return;
+        }
+    };
+
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    if let Some(res) = run_command(&config, &args) {
+        match res {
+            Ok(0) => {},
+            Ok(status) => ::std::process::exit(status),
+            Err(e) => display_error(&*e)
+        }
+        return;
+    }
+
     // check to see if running with flag /S
     if !::std::env::args().any(|s| s == "/S") {
         // if not → ask user if want to run at startup
@@ -61,33 +216,10 @@ fn main() {
         }
     }
 
-    let config = match ::std::fs::File::open("config.toml") {
-        Ok(mut f) => {
-            let mut config_text = String::new();
-            f.read_to_string(&mut config_text).unwrap();
-            match config_text.parse::<TomlValue>() {
-                Ok(c) => Some(c),
-                Err(e) => {
-                    display_error(&e);
-                    return;
-                }
-            }
-        },
-        Err(e) => match e.kind() {
-            IOErrorKind::NotFound => None,
-            _ => {
-                display_error(&e);
-                return;
-            }
-        }
-    };
-
     let app = match app::App::new(&config) {
         Ok(v) => v,
         Err(e) => {
-            let mut text = format!("Error: {}", e).encode_utf16().collect::<Vec<u16>>();
-            text.push(0); text.push(0);
-            unsafe { MessageBoxW(null_mut(), text.as_ptr(), null_mut(), MB_ICONERROR) };
+            display_error(&*e);
             return;
         }
     };
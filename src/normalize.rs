@@ -0,0 +1,333 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::{self, Read, Write};
+
+use toml::Value as TomlValue;
+
+use search::{Search, CharInfo};
+use describe::unlisted;
+
+const S_BASE: u32 = 0xAC00;
+const L_BASE: u32 = 0x1100;
+const V_BASE: u32 = 0x1161;
+const T_BASE: u32 = 0x11A7;
+const L_COUNT: u32 = 19;
+const V_COUNT: u32 = 21;
+const T_COUNT: u32 = 28;
+const N_COUNT: u32 = V_COUNT * T_COUNT;
+const S_COUNT: u32 = L_COUNT * N_COUNT;
+
+/// The forms text can be converted to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Form {
+    Nfc, Nfd, Nfkc, Nfkd,
+    Lower, Upper, Fold
+}
+
+pub const FORMS: [Form; 7] = [Form::Nfc, Form::Nfd, Form::Nfkc, Form::Nfkd, Form::Lower, Form::Upper, Form::Fold];
+
+impl Form {
+    pub fn name(self) -> &'static str {
+        match self {
+            Form::Nfc => "NFC",
+            Form::Nfd => "NFD",
+            Form::Nfkc => "NFKC",
+            Form::Nfkd => "NFKD",
+            Form::Lower => "lower",
+            Form::Upper => "upper",
+            Form::Fold => "fold"
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Form> {
+        FORMS.iter().cloned().find(|f| f.name().eq_ignore_ascii_case(s))
+    }
+}
+
+/// Normalizes and case maps text with the decomposition and case data in the index
+pub struct Normalizer<'a> {
+    search: &'a Search,
+    chars: HashMap<char, CharInfo>
+}
+
+impl<'a> Normalizer<'a> {
+    pub fn new(search: &'a Search) -> Normalizer<'a> {
+        Normalizer { search, chars: HashMap::new() }
+    }
+
+    fn info(&mut self, c: char) -> Result<&CharInfo, Box<dyn Error>> {
+        if !self.chars.contains_key(&c) {
+            let info = self.search.lookup(c as u32)?.unwrap_or_else(|| unlisted(self.search, c as u32));
+            self.chars.insert(c, info);
+        }
+        Ok(&self.chars[&c])
+    }
+
+    fn combining_class(&mut self, c: char) -> Result<u8, Box<dyn Error>> {
+        // nothing ASCII combines, and most text is mostly ASCII
+        if c.is_ascii() { return Ok(0); }
+        Ok(self.info(c)?.combining_class)
+    }
+
+    /// Fully decompose `c` onto the end of `out`
+    fn decompose(&mut self, c: char, compat: bool, out: &mut Vec<char>) -> Result<(), Box<dyn Error>> {
+        let cp = c as u32;
+        if (S_BASE..S_BASE + S_COUNT).contains(&cp) {
+            let s = cp - S_BASE;
+            out.extend([L_BASE + s / N_COUNT, V_BASE + s % N_COUNT / T_COUNT].iter().filter_map(|&j| ::std::char::from_u32(j)));
+            if s % T_COUNT != 0 {
+                out.extend(::std::char::from_u32(T_BASE + s % T_COUNT));
+            }
+            return Ok(());
+        }
+        if c.is_ascii() {
+            out.push(c);
+            return Ok(());
+        }
+        let info = self.info(c)?;
+        if info.decomposition.is_empty() || !(compat || info.decomposition_type == "can") {
+            out.push(c);
+            return Ok(());
+        }
+        let decomposition = info.decomposition.clone();
+        for d in decomposition.chars() {
+            self.decompose(d, compat, out)?;
+        }
+        Ok(())
+    }
+
+    /// Put every run of combining marks in canonical order
+    fn reorder(&mut self, chars: &mut [char]) -> Result<(), Box<dyn Error>> {
+        let classes = chars.iter().map(|&c| self.combining_class(c)).collect::<Result<Vec<_>, _>>()?;
+        let mut start = 0;
+        while start < chars.len() {
+            if classes[start] == 0 { start += 1; continue; }
+            let end = (start..chars.len()).find(|&i| classes[i] == 0).unwrap_or(chars.len());
+            let mut run = chars[start..end].iter().cloned().zip(classes[start..end].iter().cloned()).collect::<Vec<_>>();
+            // a stable sort, so marks with the same class keep their order
+            run.sort_by_key(|&(_, ccc)| ccc);
+            for (i, (c, _)) in run.into_iter().enumerate() {
+                chars[start + i] = c;
+            }
+            start = end;
+        }
+        Ok(())
+    }
+
+    fn compose_pair(&mut self, first: char, second: char) -> Result<Option<char>, Box<dyn Error>> {
+        let (a, b) = (first as u32, second as u32);
+        if (L_BASE..L_BASE + L_COUNT).contains(&a) && (V_BASE..V_BASE + V_COUNT).contains(&b) {
+            return Ok(::std::char::from_u32(S_BASE + ((a - L_BASE) * V_COUNT + (b - V_BASE)) * T_COUNT));
+        }
+        if (S_BASE..S_BASE + S_COUNT).contains(&a) && (a - S_BASE) % T_COUNT == 0 && (T_BASE + 1..T_BASE + T_COUNT).contains(&b) {
+            return Ok(::std::char::from_u32(a + b - T_BASE));
+        }
+        if first.is_ascii() && second.is_ascii() {
+            return Ok(None);
+        }
+        self.search.compose(first, second)
+    }
+
+    /// The canonical composition algorithm, on text that is already decomposed and reordered
+    fn compose(&mut self, chars: Vec<char>) -> Result<Vec<char>, Box<dyn Error>> {
+        let mut out: Vec<char> = Vec::with_capacity(chars.len());
+        let mut starter = None;
+        // the combining class of the last character since the starter, None if there isn't one
+        let mut last_class: Option<u8> = None;
+        for c in chars {
+            let class = self.combining_class(c)?;
+            if let Some(si) = starter {
+                let blocked = last_class.is_some_and(|lc| lc == 0 || lc >= class);
+                if !blocked {
+                    if let Some(composite) = self.compose_pair(out[si], c)? {
+                        out[si] = composite;
+                        continue;
+                    }
+                }
+            }
+            if class == 0 {
+                starter = Some(out.len());
+                last_class = None;
+            } else {
+                last_class = Some(class);
+            }
+            out.push(c);
+        }
+        Ok(out)
+    }
+
+    fn case_map(&mut self, c: char, form: Form) -> Result<String, Box<dyn Error>> {
+        let info = self.info(c)?;
+        let mapped = match form {
+            Form::Lower => &info.lowercase,
+            Form::Upper => &info.uppercase,
+            _ => &info.casefold
+        };
+        Ok(if mapped.is_empty() { c.to_string() } else { mapped.clone() })
+    }
+
+    /// Convert `text` to `form`. Case mapping uses the full mappings without the context sensitive
+    /// rules, so a final sigma lowercases to σ.
+    pub fn convert(&mut self, text: &str, form: Form) -> Result<String, Box<dyn Error>> {
+        match form {
+            Form::Lower | Form::Upper | Form::Fold => {
+                let mut out = String::with_capacity(text.len());
+                for c in text.chars() {
+                    out += &self.case_map(c, form)?;
+                }
+                Ok(out)
+            },
+            Form::Nfc | Form::Nfd | Form::Nfkc | Form::Nfkd => {
+                let compat = form == Form::Nfkc || form == Form::Nfkd;
+                let mut chars = Vec::with_capacity(text.len());
+                for c in text.chars() {
+                    self.decompose(c, compat, &mut chars)?;
+                }
+                self.reorder(&mut chars)?;
+                if form == Form::Nfc || form == Form::Nfkc {
+                    chars = self.compose(chars)?;
+                }
+                Ok(chars.into_iter().collect())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Change {
+    Same, Added, Removed
+}
+
+/// The codepoints that stayed, were added and were removed going from `a` to `b`, in order
+pub fn diff(a: &str, b: &str) -> Vec<(Change, char)> {
+    let (a, b) = (a.chars().collect::<Vec<_>>(), b.chars().collect::<Vec<_>>());
+    // lcs[i][j] is the length of the longest common subsequence of a[i..] and b[j..]
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut changes = Vec::with_capacity(a.len().max(b.len()));
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            changes.push((Change::Same, a[i]));
+            i += 1; j += 1;
+        } else if i < a.len() && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            changes.push((Change::Removed, a[i]));
+            i += 1;
+        } else {
+            changes.push((Change::Added, b[j]));
+            j += 1;
+        }
+    }
+    changes
+}
+
+/// Write the changes as codepoints, with the added ones marked `+` and the removed ones `-`
+pub fn format_diff(changes: &[(Change, char)]) -> String {
+    changes.iter().map(|&(change, c)| format!("{}U+{:04X}", match change {
+        Change::Same => "",
+        Change::Added => "+",
+        Change::Removed => "-"
+    }, c as u32)).collect::<Vec<_>>().join(" ")
+}
+
+/// `ununi normalize [--form FORM] [TEXT...]` shows every normalization and case mapping of the text
+/// (or stdin) with the codepoints that changed, or prints just one form so it can be piped somewhere
+pub fn run(config: &Option<TomlValue>, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut form = None;
+    let mut words = Vec::new();
+    let mut args = args.iter();
+    while let Some(a) = args.next() {
+        match a.as_str() {
+            "--form" => form = Some(args.next().and_then(|f| Form::parse(f))
+                                    .ok_or("--form needs one of: nfc, nfd, nfkc, nfkd, lower, upper, fold")?),
+            _ => words.push(a.as_str())
+        }
+    }
+    let text = if words.is_empty() {
+        let mut text = String::new();
+        io::stdin().read_to_string(&mut text)?;
+        text
+    } else {
+        words.join(" ")
+    };
+
+    let search = Search::open(config)?;
+    let mut normalizer = Normalizer::new(&search);
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    match form {
+        Some(form) => write!(out, "{}", normalizer.convert(&text, form)?)?,
+        None => for &form in FORMS.iter() {
+            let converted = normalizer.convert(&text, form)?;
+            if converted == text {
+                writeln!(out, "{:<6} {}\tunchanged", form.name(), converted)?;
+            } else {
+                writeln!(out, "{:<6} {}\t{}", form.name(), converted, format_diff(&diff(&text, &converted)))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ::normalize::*;
+    use ::search::tests::fixture;
+
+    const CHARS: &str = r#"<group blk="ASCII" gc="Ll" sc="Latn" bc="L">
+<char cp="0065" na="LATIN SMALL LETTER E" uc="0045" tc="0045"/>
+</group>
+<group blk="Latin_1_Sup" gc="Zs" sc="Zyyy" bc="CS">
+<char cp="00A0" na="NO-BREAK SPACE" dt="nob" dm="0020"/>
+</group>
+<group blk="Latin_1_Sup" gc="Ll" sc="Latn" bc="L">
+<char cp="00DF" na="LATIN SMALL LETTER SHARP S" uc="0053 0053" tc="0053 0073" cf="0073 0073"/>
+<char cp="00E9" na="LATIN SMALL LETTER E WITH ACUTE" dt="can" dm="0065 0301" uc="00C9" tc="00C9"/>
+</group>
+<group blk="Diacriticals" gc="Mn" sc="Zinh" bc="NSM">
+<char cp="0301" na="COMBINING ACUTE ACCENT" ccc="230"/>
+<char cp="0323" na="COMBINING DOT BELOW" ccc="220"/>
+<char cp="0341" na="COMBINING ACUTE TONE MARK" ccc="230" dt="can" dm="0301" Comp_Ex="Y"/>
+</group>
+<group blk="Alphabetic_PF" gc="Ll" sc="Latn" bc="L">
+<char cp="FB01" na="LATIN SMALL LIGATURE FI" dt="com" dm="0066 0069" uc="0046 0049" tc="0046 0069" cf="0066 0069"/>
+</group>
+"#;
+
+    #[test]
+    fn normalization_forms() {
+        let s = fixture(CHARS);
+        let mut n = Normalizer::new(&s);
+        assert_eq!(n.convert("caf\u{e9}", Form::Nfd).unwrap(), "cafe\u{301}");
+        assert_eq!(n.convert("cafe\u{301}", Form::Nfc).unwrap(), "caf\u{e9}");
+        // marks are put in order of their combining class, then the acute can still reach the e
+        assert_eq!(n.convert("e\u{301}\u{323}", Form::Nfd).unwrap(), "e\u{323}\u{301}");
+        assert_eq!(n.convert("e\u{301}\u{323}", Form::Nfc).unwrap(), "\u{e9}\u{323}");
+        // excluded from composition
+        assert_eq!(n.convert("e\u{341}", Form::Nfc).unwrap(), "\u{e9}");
+        assert_eq!(n.convert("\u{fb01}\u{a0}", Form::Nfc).unwrap(), "\u{fb01}\u{a0}");
+        assert_eq!(n.convert("\u{fb01}\u{a0}", Form::Nfkc).unwrap(), "fi ");
+        assert_eq!(n.convert("\u{d55c}", Form::Nfd).unwrap(), "\u{1112}\u{1161}\u{11ab}");
+        assert_eq!(n.convert("\u{1112}\u{1161}\u{11ab}", Form::Nfc).unwrap(), "\u{d55c}");
+    }
+
+    #[test]
+    fn case_mapping() {
+        let s = fixture(CHARS);
+        let mut n = Normalizer::new(&s);
+        assert_eq!(n.convert("\u{df}e", Form::Upper).unwrap(), "SSE");
+        assert_eq!(n.convert("AB\u{fb01}", Form::Fold).unwrap(), "abfi");
+        assert_eq!(n.convert("AB", Form::Lower).unwrap(), "ab");
+        assert_eq!(Form::parse("nfkc"), Some(Form::Nfkc));
+    }
+
+    #[test]
+    fn diffs() {
+        assert_eq!(format_diff(&diff("cafe\u{301}", "caf\u{e9}")), "U+0063 U+0061 U+0066 -U+0065 -U+0301 +U+00E9");
+        assert_eq!(format_diff(&diff("ab", "ab")), "U+0061 U+0062");
+    }
+}
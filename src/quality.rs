@@ -0,0 +1,120 @@
+use std::error::Error;
+
+use toml::Value as TomlValue;
+
+use search::Search;
+
+/// A query as people type it, with the characters they are after
+pub struct Case {
+    pub query: &'static str,
+    /// the results that count as right, any of them at the top is a perfect answer
+    pub relevant: &'static [u32]
+}
+
+/// Queries our users make, with what they expect to find
+pub const CORPUS: &[Case] = &[
+    Case { query: "rarr", relevant: &[0x2192] },
+    Case { query: "rightwards arrow", relevant: &[0x2192] },
+    Case { query: "arow", relevant: &[0x2192, 0x2190] },
+    Case { query: "right arrow", relevant: &[0x2192] },
+    Case { query: "double arrow", relevant: &[0x21d2, 0x21d4] },
+    Case { query: "em dash", relevant: &[0x2014] },
+    Case { query: "en dash", relevant: &[0x2013] },
+    Case { query: "dash", relevant: &[0x2014, 0x2013] },
+    Case { query: "degree", relevant: &[0xb0] },
+    Case { query: "celsius", relevant: &[0x2103] },
+    Case { query: "check mark", relevant: &[0x2713, 0x2714] },
+    Case { query: "check", relevant: &[0x2713, 0x2714, 0x2705] },
+    Case { query: "lambda", relevant: &[0x3bb] },
+    Case { query: "lamda", relevant: &[0x3bb, 0x39b] },
+    Case { query: "nbsp", relevant: &[0xa0] },
+    Case { query: "no-break space", relevant: &[0xa0] },
+    Case { query: "zwsp", relevant: &[0x200b] },
+    Case { query: "ellipsis", relevant: &[0x2026] },
+    Case { query: "ellipses", relevant: &[0x2026] },
+    Case { query: "bullet", relevant: &[0x2022] },
+    Case { query: "multiplication", relevant: &[0xd7] },
+    Case { query: "copyright", relevant: &[0xa9] },
+    Case { query: "euro", relevant: &[0x20ac] },
+    Case { query: "pi", relevant: &[0x3c0] },
+    Case { query: "infinity", relevant: &[0x221e] },
+    Case { query: "not equal", relevant: &[0x2260] },
+    Case { query: "thumbs up", relevant: &[0x1f44d] },
+    Case { query: "e acute", relevant: &[0xe9] },
+    Case { query: "left quote", relevant: &[0x201c, 0x2018] },
+    Case { query: "shrug", relevant: &[0x1f937] },
+    Case { query: "heart", relevant: &[0x2764, 0x2665] }
+];
+
+/// How well a search answers the corpus
+#[derive(Debug)]
+pub struct Report {
+    /// the mean of one over the rank of the first right result, counting none as 0
+    pub mrr: f64,
+    /// the mean share of the right results that are in the first `k`
+    pub recall: f64,
+    pub k: usize,
+    /// each query with the rank of its first right result, if any is in the first `k`
+    pub ranks: Vec<(&'static str, Option<usize>)>
+}
+
+/// Run every case of `corpus` through `search`, looking at the first `k` results of each
+pub fn evaluate(search: &Search, corpus: &[Case], k: usize) -> Result<Report, Box<dyn Error>> {
+    let mut ranks = Vec::new();
+    let (mut rr, mut recall) = (0.0, 0.0);
+    for case in corpus {
+        let found = search.query(case.query, k)?.iter().map(|c| c.cp).collect::<Vec<_>>();
+        let rank = found.iter().position(|cp| case.relevant.contains(cp)).map(|i| i + 1);
+        rr += rank.map_or(0.0, |r| 1.0 / r as f64);
+        recall += case.relevant.iter().filter(|cp| found.contains(cp)).count() as f64 / case.relevant.len() as f64;
+        ranks.push((case.query, rank));
+    }
+    let n = corpus.len().max(1) as f64;
+    Ok(Report { mrr: rr / n, recall: recall / n, k, ranks })
+}
+
+/// `ununi quality [-k N]` measures the search of the real index against the corpus
+pub fn run(config: &Option<TomlValue>, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let k = match args {
+        [] => 10,
+        [flag, n] if flag == "-k" => n.parse().map_err(|_| "-k needs a number of results")?,
+        _ => return Err("usage: ununi quality [-k N]".into())
+    };
+    let report = evaluate(&Search::open(config)?, CORPUS, k)?;
+    for (query, rank) in &report.ranks {
+        println!("{:<24} {}", query, rank.map_or(String::from("-"), |r| r.to_string()));
+    }
+    println!("MRR {:.3}, recall@{} {:.3}", report.mrr, report.k, report.recall);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ::quality::*;
+    use ::search::tests::search_from_xml;
+
+    /// The characters of the corpus along with the ones they get mixed up with, as they are in the UCD
+    const QUALITY_FIXTURE: &str = include_str!("quality.xml");
+
+    /// Ranking changes may not make the search worse than this. `rarr` finds nothing yet,
+    /// raise these as the search gets better.
+    const MIN_MRR: f64 = 0.90;
+    const MIN_RECALL: f64 = 0.96;
+
+    #[test]
+    fn search_quality() {
+        let s = search_from_xml(QUALITY_FIXTURE);
+        let report = evaluate(&s, CORPUS, 10).unwrap();
+        assert!(report.mrr >= MIN_MRR, "MRR fell to {:.3}, ranks {:?}", report.mrr, report.ranks);
+        assert!(report.recall >= MIN_RECALL, "recall@10 fell to {:.3}, ranks {:?}", report.recall, report.ranks);
+    }
+
+    #[test]
+    fn metrics() {
+        let s = search_from_xml(QUALITY_FIXTURE);
+        let corpus = [Case { query: "em dash", relevant: &[0x2014] }, Case { query: "nothing like it", relevant: &[0x2014] }];
+        let report = evaluate(&s, &corpus, 10).unwrap();
+        assert_eq!(report.ranks, vec![("em dash", Some(1)), ("nothing like it", None)]);
+        assert_eq!((report.mrr, report.recall), (0.5, 0.5));
+    }
+}
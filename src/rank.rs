@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+
+use toml::Value as TomlValue;
+
+use search::CharInfo;
+
+/// How often characters are picked, shipped with ununi
+const FREQUENCY_TABLE: &str = include_str!("frequency.txt");
+
+/// Read the `codepoint weight` lines of the frequency table into weights from 0 to 1
+fn parse_frequencies(table: &str) -> HashMap<u32, f32> {
+    table.lines()
+        .filter_map(|line| {
+            let mut fields = line.split('#').next().unwrap_or("").split_whitespace();
+            let cp = u32::from_str_radix(fields.next()?, 16).ok()?;
+            let weight = fields.next()?.parse::<f32>().ok()?;
+            Some((cp, weight.clamp(0.0, 100.0) / 100.0))
+        })
+        .collect()
+}
+
+/// The runs of letters and digits in `text`, lowercased
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()).map(str::to_lowercase)
+}
+
+/// The plain words of a query, lowercased, leaving out field queries, excluded words and the boolean operators
+pub fn query_words(query: &str) -> Vec<String> {
+    query.split_whitespace()
+        .filter(|t| !t.contains(':') && !t.starts_with('-') && !["AND", "OR", "NOT"].contains(t))
+        .flat_map(words)
+        .collect()
+}
+
+/// The words of a name or alias as `query_words` would give them
+fn name_words(name: &str) -> Vec<String> {
+    words(name).collect()
+}
+
+/// How search results are ordered on top of the index's BM25 scores. The weights can be changed in the `[ranking]`
+/// table of the configuration.
+#[derive(Debug, Clone)]
+pub struct Ranking {
+    /// how much more a match in the name or an alias counts than one in the block
+    pub name_boost: f32,
+    /// added when the query is the whole name or an alias
+    pub exact: f32,
+    /// added in proportion to how many of the query's words are whole words of the name
+    pub whole_words: f32,
+    /// taken off in proportion to how much of the name is words that aren't in the query
+    pub length: f32,
+    /// added in proportion to how often the character is picked
+    pub frequency: f32,
+    frequencies: HashMap<u32, f32>
+}
+
+impl Default for Ranking {
+    fn default() -> Ranking {
+        Ranking {
+            name_boost: 3.0, exact: 10.0, whole_words: 4.0, length: 3.0, frequency: 2.0,
+            frequencies: parse_frequencies(FREQUENCY_TABLE)
+        }
+    }
+}
+
+impl Ranking {
+    pub fn from_config(config: &Option<TomlValue>) -> Ranking {
+        let mut ranking = Ranking::default();
+        let table = match config.as_ref().and_then(|c| c.get("ranking")) { Some(t) => t, None => return ranking };
+        for (key, weight) in &mut [("name_boost", &mut ranking.name_boost), ("exact", &mut ranking.exact),
+                                   ("whole_words", &mut ranking.whole_words), ("length", &mut ranking.length),
+                                   ("frequency", &mut ranking.frequency)] {
+            // whole numbers are fine too
+            let value = table.get(*key).and_then(|v| v.as_float().or_else(|| v.as_integer().map(|i| i as f64)));
+            if let Some(value) = value { **weight = value as f32; }
+        }
+        ranking
+    }
+
+    /// How often `cp` is picked, from 0 for characters that aren't in the table to 1
+    pub fn frequency_of(&self, cp: u32) -> f32 {
+        self.frequencies.get(&cp).cloned().unwrap_or(0.0)
+    }
+
+    /// The score of a result given the index's score for it and the words of the query
+    pub fn score(&self, index_score: f32, info: &CharInfo, words: &[String]) -> f32 {
+        let name = name_words(&info.name);
+        let mut score = index_score + self.frequency * self.frequency_of(info.cp);
+        if words.is_empty() { return score; }
+        if name == words || info.aliases.iter().any(|a| name_words(a) == words) {
+            score += self.exact;
+        }
+        let whole = name.iter().filter(|n| words.contains(n)).count();
+        score += self.whole_words * words.iter().filter(|w| name.contains(w)).count() as f32 / words.len() as f32;
+        score - self.length * (name.len() - whole) as f32 / name.len().max(1) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::rank::*;
+    use ::search::tests::search_from_xml;
+
+    /// Real characters whose plain BM25 order was poor
+    const RANKING_FIXTURE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<ucd xmlns="http://www.unicode.org/ns/2003/ucd/1.0">
+<repertoire>
+<group blk="Latin_1_Sup" gc="Sc" sc="Zyyy" bc="ET">
+<char cp="00A4" na="CURRENCY SIGN"/>
+</group>
+<group blk="Currency_Symbols" gc="Sc" sc="Zyyy" bc="ET">
+<char cp="20A0" na="EURO-CURRENCY SIGN"/>
+<char cp="20AC" na="EURO SIGN"/>
+<char cp="20BF" na="BITCOIN SIGN"/>
+</group>
+<group blk="Punctuation" gc="Pd" sc="Zyyy" bc="ON">
+<char cp="2012" na="FIGURE DASH"/>
+<char cp="2013" na="EN DASH"/>
+<char cp="2014" na="EM DASH"/>
+</group>
+<group blk="Arrows" gc="Sm" sc="Zyyy" bc="ON">
+<char cp="2190" na="LEFTWARDS ARROW"/>
+<char cp="219A" na="LEFTWARDS ARROW WITH STROKE"/>
+<char cp="21A2" na="LEFTWARDS ARROW WITH TAIL"/>
+</group>
+<group blk="Sup_Arrows_B" gc="Sm" sc="Zyyy" bc="ON">
+<char cp="2914" na="LEFTWARDS ARROW WITH TAIL WITH DOUBLE VERTICAL STROKE"/>
+<char cp="2B05" na="LEFTWARDS BLACK ARROW"/>
+</group>
+</repertoire>
+</ucd>
+"#;
+
+    fn ranked(query: &str) -> Vec<u32> {
+        search_from_xml(RANKING_FIXTURE).query(query, 10).unwrap().iter().map(|c| c.cp).collect()
+    }
+
+    #[test]
+    fn shorter_and_exact_names_first() {
+        assert_eq!(ranked("leftwards arrow"), vec![0x2190, 0x2b05, 0x219a, 0x21a2, 0x2914]);
+        // every arrow matches, but the ones with a tail come first
+        assert_eq!(ranked("arrow tail")[..2].to_vec(), vec![0x21a2, 0x2914]);
+    }
+
+    #[test]
+    fn names_over_blocks() {
+        // every sign in the currency symbols block matches, but the ones named after currency come first
+        assert_eq!(ranked("currency")[..2].to_vec(), vec![0xa4, 0x20a0]);
+    }
+
+    #[test]
+    fn common_characters_first() {
+        assert_eq!(ranked("dash"), vec![0x2014, 0x2013, 0x2012]);
+        assert_eq!(ranked("sign")[0], 0x20ac);
+    }
+
+    #[test]
+    fn pages_ranked_alike() {
+        let s = search_from_xml(RANKING_FIXTURE);
+        let pages = (0..5).flat_map(|i| s.query_page("leftwards arrow", i, 1).unwrap().chars).map(|c| c.cp).collect::<Vec<_>>();
+        assert_eq!(pages, ranked("leftwards arrow"));
+    }
+
+    #[test]
+    fn words_and_config() {
+        assert_eq!(query_words("blck:Arrows leftwards -double AND \"no-break\""), vec!["leftwards", "no", "break"]);
+        assert_eq!(name_words("LINE FEED (LF)"), query_words("line feed (lf)"));
+        let config = Some("[ranking]\nfrequency = 0\nexact = 2.5".parse::<TomlValue>().unwrap());
+        let ranking = Ranking::from_config(&config);
+        assert_eq!((ranking.frequency, ranking.exact, ranking.length), (0.0, 2.5, 3.0));
+        assert!(ranking.frequency_of(0x2192) > ranking.frequency_of(0x2193));
+        assert_eq!(ranking.frequency_of(0x2914), 0.0);
+    }
+}
@@ -0,0 +1,1145 @@
+use std::fs;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::error::Error;
+use std::io::{Cursor, ErrorKind as IOErrorKind, copy, Seek, SeekFrom, Read, Write};
+
+use tantivy::{Index, IndexReader, Document};
+use tantivy::schema::*;
+use tantivy::collector::{TopDocs, Count};
+use tantivy::query::{QueryParser, QueryParserError, TermQuery, RangeQuery, BooleanQuery, Occur, Query};
+
+use rank::{Ranking, query_words};
+use fuzzy::Fuzzy;
+
+use levenshtein_automata::LevenshteinAutomatonBuilder;
+
+use xml::attribute::OwnedAttribute;
+use xml::reader::{EventReader, XmlEvent};
+
+use toml::Value as TomlValue;
+
+#[derive(Debug)]
+pub struct TError(pub tantivy::TantivyError);
+
+impl std::error::Error for TError {}
+
+impl std::fmt::Display for TError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        self.0.fmt(f)
+    }
+}
+
+impl From<tantivy::TantivyError> for TError {
+    fn from(e: tantivy::TantivyError) -> Self {
+        TError(e)
+    }
+}
+
+/// A query that couldn't be parsed, with what is wrong with it in words and where, in characters of the query as it
+/// was typed, when that can be told
+#[derive(Debug)]
+pub struct QError {
+    pub message: String,
+    pub position: Option<usize>
+}
+
+impl std::error::Error for QError {}
+
+impl std::fmt::Display for QError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        match self.position {
+            Some(p) => write!(f, "{} at character {}", self.message, p + 1),
+            None => f.write_str(&self.message)
+        }
+    }
+}
+
+impl QError {
+    /// Explain why Tantivy couldn't parse `query`. It only says there is a syntax error, not where,
+    /// so the query is looked over again for the usual mistakes. Anything else it rejects, like an operator with
+    /// nothing after it, is only said to be unparsable, without a position.
+    pub fn new(query: &str, error: QueryParserError) -> QError {
+        let char_at = |byte: usize| query[..byte].chars().count();
+        // where a field name is used, as `name:`
+        let field_at = |name: &str| query.match_indices(&format!("{}:", name))
+            .find(|&(i, _)| query[..i].chars().next_back().map_or(true, |c| !c.is_alphanumeric() && c != '_'))
+            .map(|(i, _)| char_at(i));
+        let (message, position) = match error {
+            QueryParserError::SyntaxError => match syntax_error(query) {
+                Some((position, message)) => (message.to_owned(), Some(position)),
+                None => ("the query can't be parsed".to_owned(), None)
+            },
+            QueryParserError::FieldDoesNotExist(ref name) => (format!("there is no field named `{}`", name), field_at(name)),
+            QueryParserError::FieldNotIndexed(ref name) => (format!("the field `{}` can't be searched", name), field_at(name)),
+            QueryParserError::ExpectedInt(_) => ("expected a number".to_owned(), field_at("codepnt")),
+            QueryParserError::AllButQueryForbidden =>
+                ("the query only leaves things out, it needs something to look for".to_owned(), query.find('-').map(char_at)),
+            ref e => (e.to_string(), None)
+        };
+        QError { message, position }
+    }
+}
+
+/// Where the query language's syntax is broken, as a character position and what is wrong there
+fn syntax_error(query: &str) -> Option<(usize, &'static str)> {
+    let chars = query.chars().collect::<Vec<_>>();
+    let mut quote = None;
+    let mut parens = Vec::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if quote.is_some() {
+            if c == '"' { quote = None; }
+            continue;
+        }
+        let before = if i == 0 { None } else { Some(chars[i - 1]) };
+        let after = chars.get(i + 1).cloned();
+        match c {
+            '"' if after == Some('"') => return Some((i, "empty quotes")),
+            '"' => quote = Some(i),
+            '(' => parens.push(i),
+            ')' if parens.pop().is_none() => return Some((i, "a closing parenthesis without an opening one")),
+            ':' if before.map_or(true, |b| !b.is_alphanumeric() && b != '_') => return Some((i, "a `:` without a field name before it")),
+            ':' if after.map_or(true, |a| a.is_whitespace() || a == ')') => return Some((i, "nothing to search for after the `:`")),
+            ':' if after == Some(':') => return Some((i + 1, "a `:` too many")),
+            _ => {}
+        }
+    }
+    match (quote, parens.last()) {
+        (Some(q), _) => Some((q, "a quote that isn't closed")),
+        (None, Some(&p)) => Some((p, "a parenthesis that isn't closed")),
+        (None, None) => None
+    }
+}
+
+/// The words of a query with everything that means something in the query language left out, so it can't fail to parse
+pub fn literal_query(query: &str) -> String {
+    query.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty())
+        // and the operators are only operators in capitals
+        .map(str::to_lowercase).collect::<Vec<_>>().join(" ")
+}
+
+/// A single character as stored in the index
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CharInfo {
+    pub cp: u32,
+    pub name: String,
+    pub block: String,
+    /// the general category, ex. `Lu` or `Sm`
+    pub category: String,
+    /// other names from the UCD: corrections, control names, abbreviations like `NBSP`...
+    pub aliases: Vec<String>,
+    /// the script as an ISO 15924 code, ex. `Latn`, or `Zyyy` for characters shared between scripts
+    pub script: String,
+    /// the bidirectional class, ex. `L` or `RLO`
+    pub bidi_class: String,
+    /// the Grapheme_Cluster_Break property as the UCD abbreviates it, ex. `EX` for Extend or `XX` for Other
+    pub grapheme_break: String,
+    pub extended_pictographic: bool,
+    /// the Indic_Conjunct_Break property, `Consonant`, `Linker`, `Extend` or `None`
+    pub indic_conjunct_break: String,
+    /// the canonical combining class, 0 for everything but combining marks
+    pub combining_class: u8,
+    /// the characters this one decomposes into, empty if it doesn't
+    pub decomposition: String,
+    /// `can` for canonical decompositions, otherwise the kind of compatibility decomposition, ex. `font` or `super`
+    pub decomposition_type: String,
+    /// the full case mappings, empty when the character maps to itself
+    pub uppercase: String,
+    pub lowercase: String,
+    pub titlecase: String,
+    pub casefold: String,
+    /// the version of Unicode the character was added in, ex. `1.1`
+    pub age: String
+}
+
+impl CharInfo {
+    pub fn chr(&self) -> Option<char> {
+        ::std::char::from_u32(self.cp)
+    }
+
+    /// The shortest name for the character, usually its abbreviation
+    pub fn short_name(&self) -> &str {
+        self.aliases.iter().chain(Some(&self.name)).filter(|n| !n.is_empty()).min_by_key(|n| n.len()).map_or("", |n| n.as_str())
+    }
+
+    /// The codepoint and name, with the short name if there is one, ex. `U+00A0 NO-BREAK SPACE (NBSP)`
+    pub fn describe(&self) -> String {
+        let short = self.short_name();
+        if short == self.name {
+            format!("U+{:04X} {}", self.cp, self.name)
+        } else {
+            format!("U+{:04X} {} ({})", self.cp, self.name, short)
+        }
+    }
+
+    /// Characters that most editors show as nothing, or as something they aren't
+    pub fn is_invisible(&self) -> bool {
+        match self.category.as_str() {
+            "Cf" | "Zl" | "Zp" => true,
+            "Zs" => self.cp != 0x20,
+            "Cc" => self.cp != 0x09 && self.cp != 0x0a && self.cp != 0x0d,
+            _ => false
+        }
+    }
+
+    /// Explicit bidirectional formatting characters, which can reorder how text is displayed
+    pub fn is_bidi_control(&self) -> bool {
+        // the embeddings, overrides and isolates, plus the implicit marks
+        matches!(self.bidi_class.as_str(), "LRE" | "RLE" | "LRO" | "RLO" | "PDF" | "LRI" | "RLI" | "FSI" | "PDI") ||
+            matches!(self.cp, 0x061C | 0x200E | 0x200F)
+    }
+}
+
+/// A block from the UCD's list of them, which covers its whole range, assigned or not
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub first: u32,
+    pub last: u32,
+    pub name: String
+}
+
+/// Characters the UCD only gives as a range, like CJK and Tangut ideographs, Hangul syllables or private use,
+/// which all have the same properties and names that follow from their codepoints
+#[derive(Debug, Clone, PartialEq)]
+pub struct Range {
+    pub first: u32,
+    pub last: u32,
+    /// the name with `#` in place of the codepoint, ex. `TANGUT IDEOGRAPH-#`, or empty when there isn't one
+    pub name: String,
+    pub block: String,
+    pub category: String,
+    pub script: String,
+    pub bidi_class: String,
+    pub age: String
+}
+
+impl Range {
+    /// What the range says about `cp`, which is in it
+    pub fn char_info(&self, cp: u32) -> CharInfo {
+        CharInfo {
+            cp, name: self.name.replace('#', &format!("{:04X}", cp)), block: self.block.clone(), category: self.category.clone(),
+            script: self.script.clone(), bidi_class: self.bidi_class.clone(), grapheme_break: String::from("XX"),
+            indic_conjunct_break: String::from("None"), decomposition_type: String::from("none"), age: self.age.clone(),
+            ..CharInfo::default()
+        }
+    }
+}
+
+/// Some of the results of a query, with how many there are in all
+#[derive(Debug)]
+pub struct Page {
+    pub chars: Vec<CharInfo>,
+    pub total: usize
+}
+
+const INDEX_VERSION: u32 = 16;
+
+/// Where the blocks are kept next to the index, since they aren't characters
+const BLOCKS_PATH: &str = "./blocks.txt";
+/// Where the ranges are kept next to the index, since their characters aren't in it one by one
+const RANGES_PATH: &str = "./ranges.txt";
+
+/// Save the blocks as `0000..007F; Basic Latin` lines, like the UCD's Blocks.txt
+fn write_blocks(path: &str, blocks: &[Block]) -> Result<(), Box<dyn Error>> {
+    let mut f = File::create(path)?;
+    for b in blocks {
+        writeln!(f, "{:04X}..{:04X}; {}", b.first, b.last, b.name)?;
+    }
+    Ok(())
+}
+
+/// Read the blocks `write_blocks` saved, or none if there aren't any
+fn read_blocks(path: &str) -> Result<Vec<Block>, Box<dyn Error>> {
+    let f = match File::open(path) {
+        Ok(f) => f,
+        Err(ref e) if e.kind() == IOErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(Box::new(e))
+    };
+    let mut blocks = Vec::new();
+    for line in BufReader::new(f).lines() {
+        let line = line?;
+        let mut parts = line.splitn(2, "; ");
+        let (range, name) = match (parts.next(), parts.next()) {
+            (Some(range), Some(name)) => (range, name),
+            _ => continue
+        };
+        let mut range = range.split("..").map(|hex| u32::from_str_radix(hex, 16));
+        if let (Some(Ok(first)), Some(Ok(last))) = (range.next(), range.next()) {
+            blocks.push(Block { first, last, name: name.to_owned() });
+        }
+    }
+    Ok(blocks)
+}
+
+fn schema() -> Schema {
+    let mut schb = SchemaBuilder::default();
+    schb.add_text_field("name", TEXT | STORED);
+    // the starts of the words of the name, see `word_prefixes`
+    schb.add_text_field("nameprefix", TEXT);
+    schb.add_text_field("blck", TEXT | STORED);
+    schb.add_u64_field("codepnt", INDEXED | STORED);
+    schb.add_text_field("gc", STRING | STORED);
+    schb.add_text_field("alias", TEXT | STORED);
+    schb.add_text_field("sc", STRING | STORED);
+    schb.add_text_field("bc", STRING | STORED);
+    schb.add_text_field("gcb", STRING | STORED);
+    schb.add_text_field("extpict", STRING | STORED);
+    schb.add_text_field("incb", STRING | STORED);
+    schb.add_u64_field("ccc", STORED);
+    schb.add_text_field("dm", STRING | STORED);
+    schb.add_text_field("dt", STRING | STORED);
+    schb.add_text_field("compex", STRING);
+    schb.add_text_field("uc", STORED);
+    schb.add_text_field("lc", STORED);
+    schb.add_text_field("tc", STORED);
+    schb.add_text_field("cf", STORED);
+    schb.add_text_field("age", STRING | STORED);
+    schb.build()
+}
+
+/// Fetch the whole body at `url`, following redirects; anything but a 200 response is an error
+fn download(url: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    use curl::easy::Easy as Curl;
+    let mut curl = Curl::new();
+    let mut buffer = Vec::new();
+    curl.url(url)?;
+    curl.follow_location(true)?;
+    {
+        let mut transfer = curl.transfer();
+        transfer.write_function(|data| { buffer.extend_from_slice(data); Ok(data.len()) })?;
+        transfer.perform()?;
+    }
+    match curl.response_code()? {
+        200 => Ok(buffer),
+        code => Err(format!("downloading {} failed with HTTP status {}", url, code).into())
+    }
+}
+
+/// Write `path` by filling `write` in a file next to it and renaming that into place once it is complete,
+/// so a download that fails part way is never taken for the real file the next time
+fn write_whole<F>(path: &str, write: F) -> Result<File, Box<dyn Error>>
+    where F: FnOnce(&mut File) -> Result<(), Box<dyn Error>>
+{
+    let partial = format!("{}.part", path);
+    let result = File::create(&partial).map_err(Box::from).and_then(|mut f| write(&mut f));
+    if let Err(e) = result {
+        let _ = fs::remove_file(&partial);
+        return Err(e);
+    }
+    fs::rename(&partial, path)?;
+    Ok(File::open(path)?)
+}
+
+/// Open a data file in the current directory, downloading it from `url` first if it isn't there
+pub fn data_file(path: &str, url: &str) -> Result<File, Box<dyn Error>> {
+    match File::open(path) {
+        Ok(f) => Ok(f),
+        Err(e) => match e.kind() {
+            IOErrorKind::NotFound => {
+                let buffer = download(url)?;
+                write_whole(path, |f| Ok(f.write_all(&buffer)?))
+            },
+            _ => Err(Box::new(e))
+        }
+    }
+}
+
+/// Open the UCD XML file in the current directory, downloading it first if it isn't there
+fn ucd_xml() -> Result<File, Box<dyn Error>> {
+    match File::open("./ucd.nounihan.grouped.xml") {
+        Ok(f) => Ok(f),
+        Err(e) => match e.kind() {
+            IOErrorKind::NotFound => {
+                // download latest UCD xml
+                let buffer = download("https://www.unicode.org/Public/UCD/latest/ucdxml/ucd.nounihan.grouped.zip")?;
+                use zip::read::*;
+                let mut zip = ZipArchive::new(Cursor::new(&buffer))?;
+                let mut archf = zip.by_index(0)?; // Unicode archives only have 1 file in them
+                write_whole("./ucd.nounihan.grouped.xml", |f| { copy(&mut archf, f)?; Ok(()) })
+            },
+            _ => Err(Box::new(e))
+        }
+    }
+}
+
+/// Every start of every word of `name` that is shorter than the word, ex. `a ar arr arro` for `ARROW`,
+/// so the word being typed can match before it is finished
+fn word_prefixes(name: &str) -> String {
+    let mut prefixes = Vec::new();
+    for word in name.split(|c: char| !c.is_alphanumeric()) {
+        prefixes.extend(word.char_indices().skip(1).map(|(i, _)| &word[..i]));
+    }
+    prefixes.join(" ")
+}
+
+/// The byte offset the last word of `query` starts at, just past the last whitespace, which may be more than a byte
+fn last_word_start(query: &str) -> usize {
+    query.char_indices().rev().find(|&(_, c)| c.is_whitespace()).map_or(0, |(i, c)| i + c.len_utf8())
+}
+
+/// The last word of the query if it is a plain word that may still be being typed. None when the query ends in
+/// a space, an operator or anything but letters and digits.
+pub fn last_word(query: &str) -> Option<&str> {
+    let last = &query[last_word_start(query)..];
+    if last.is_empty() || !last.chars().all(char::is_alphanumeric) || ["AND", "OR", "NOT"].contains(&last) {
+        return None;
+    }
+    Some(last)
+}
+
+/// The query with its last word also matched as the start of a word of a name, because it may not be typed out yet.
+/// A query that ends in a space, or in anything but a plain word, is left as it is.
+fn with_prefix(query: &str) -> String {
+    match last_word(query) {
+        Some(last) => format!("{}({} OR nameprefix:{})", &query[..query.len() - last.len()], last, last),
+        None => query.to_owned()
+    }
+}
+
+/// Where character `position` of `with_prefix(query)` is in `query`, the search for the prefix pointing back at the
+/// word it was added for
+fn typed_position(query: &str, position: usize) -> usize {
+    if with_prefix(query) == query { return position; }
+    let start = query[..last_word_start(query)].chars().count();
+    let len = query.chars().count() - start;
+    // the word became `(word OR nameprefix:word)`
+    match position.checked_sub(start + 1) {
+        None => position.min(start),
+        Some(p) if p < len => start + p,
+        Some(p) => start + p.saturating_sub(len + " OR nameprefix:".len()).min(len)
+    }
+}
+
+/// Look up an attribute of a `char` element, falling back to the attributes of its `group`
+fn ucd_attr<'a>(atrib: &'a [OwnedAttribute], group: &'a [OwnedAttribute], name: &str) -> Option<&'a str> {
+    atrib.iter().chain(group.iter()).find(|a| a.name.local_name == name).map(|a| a.value.as_str())
+}
+
+/// Convert a UCD codepoint sequence attribute like `0065 0301` to the characters, with `#` meaning the character itself
+fn ucd_chars(value: &str, cp: &str) -> Result<String, Box<dyn Error>> {
+    if value == "#" || value == cp { return Ok(String::new()); }
+    value.split_whitespace()
+        .map(|hex| u32::from_str_radix(hex, 16).ok().and_then(::std::char::from_u32).ok_or_else(|| format!("bad codepoint {} in the UCD", hex).into()))
+        .collect()
+}
+
+/// Save the ranges as `AC00..D7A3; Hangul; Lo; Hang; L; 2.0; NAME-#` lines, the name last since it can be empty
+fn write_ranges(path: &str, ranges: &[Range]) -> Result<(), Box<dyn Error>> {
+    let mut f = File::create(path)?;
+    for r in ranges {
+        writeln!(f, "{:04X}..{:04X}; {}; {}; {}; {}; {}; {}", r.first, r.last, r.block, r.category, r.script, r.bidi_class, r.age, r.name)?;
+    }
+    Ok(())
+}
+
+/// Read the ranges `write_ranges` saved, or none if there aren't any
+fn read_ranges(path: &str) -> Result<Vec<Range>, Box<dyn Error>> {
+    let f = match File::open(path) {
+        Ok(f) => f,
+        Err(ref e) if e.kind() == IOErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(Box::new(e))
+    };
+    let mut ranges = Vec::new();
+    for line in BufReader::new(f).lines() {
+        let line = line?;
+        let parts = line.splitn(7, "; ").collect::<Vec<_>>();
+        if parts.len() < 7 { continue; }
+        let mut range = parts[0].split("..").map(|hex| u32::from_str_radix(hex, 16));
+        if let (Some(Ok(first)), Some(Ok(last))) = (range.next(), range.next()) {
+            let s = |i: usize| parts[i].to_owned();
+            ranges.push(Range { first, last, block: s(1), category: s(2), script: s(3), bidi_class: s(4), age: s(5), name: s(6) });
+        }
+    }
+    Ok(ranges)
+}
+
+/// Fill `index` with one document per character described by the UCD XML in `source`,
+/// returning the blocks it lists and the characters it only gives as ranges
+pub fn build_index<R: Read>(index: &Index, source: R) -> Result<(Vec<Block>, Vec<Range>), Box<dyn Error>> {
+    let schema = index.schema();
+    let namef = schema.get_field("name").unwrap();
+    let prefixf = schema.get_field("nameprefix").unwrap();
+    let blckf = schema.get_field("blck").unwrap();
+    let cpnf  = schema.get_field("codepnt").unwrap();
+    let gcf   = schema.get_field("gc").unwrap();
+    let aliasf = schema.get_field("alias").unwrap();
+    let scf   = schema.get_field("sc").unwrap();
+    let bcf   = schema.get_field("bc").unwrap();
+    let gcbf  = schema.get_field("gcb").unwrap();
+    let epf   = schema.get_field("extpict").unwrap();
+    let incbf = schema.get_field("incb").unwrap();
+    let cccf  = schema.get_field("ccc").unwrap();
+    let dmf   = schema.get_field("dm").unwrap();
+    let dtf   = schema.get_field("dt").unwrap();
+    let compexf = schema.get_field("compex").unwrap();
+    let case_fields = ["uc", "lc", "tc", "cf"].iter().map(|f| (*f, schema.get_field(f).unwrap())).collect::<Vec<_>>();
+    let agef  = schema.get_field("age").unwrap();
+
+    let mut ixw = index.writer(50_000_000).map_err(TError)?;
+    let parser = EventReader::new(BufReader::new(source));
+    let mut group = Vec::new();
+    // the current character, which isn't finished until its name aliases have been read
+    let mut doc: Option<Document> = None;
+    let mut blocks = Vec::new();
+    let mut ranges = Vec::new();
+    for e in parser {
+        match e {
+            Ok(XmlEvent::StartElement { name, attributes: atrib, .. }) => {
+                match name.local_name.as_str() {
+                    "group" => {
+                        group = atrib;
+                        eprintln!("processing {}", ucd_attr(&[], &group, "blk").unwrap_or("NONE"));
+                    },
+                    "char" => {
+                        let cp = match ucd_attr(&atrib, &[], "cp") {
+                            Some(cp) => cp,
+                            // ranges of characters with algorithmic names (CJK ideographs, Hangul syllables...) or none at all
+                            // (private use, surrogates) only have first-cp/last-cp
+                            None => {
+                                let cp = |attr| ucd_attr(&atrib, &[], attr).and_then(|hex| u32::from_str_radix(hex, 16).ok());
+                                if let (Some(first), Some(last)) = (cp("first-cp"), cp("last-cp")) {
+                                    let attr = |name, default| ucd_attr(&atrib, &group, name).unwrap_or(default).to_owned();
+                                    ranges.push(Range {
+                                        first, last, name: attr("na", ""), block: attr("blk", "NONE"), category: attr("gc", "Cn"),
+                                        script: attr("sc", "Zzzz"), bidi_class: attr("bc", "L"), age: attr("age", "unassigned")
+                                    });
+                                }
+                                continue;
+                            }
+                        };
+                        let mut d = Document::default();
+                        d.add_text(blckf, ucd_attr(&atrib, &group, "blk").unwrap_or("NONE"));
+                        d.add_u64(cpnf, u64::from_str_radix(cp, 16)?);
+                        let name = ucd_attr(&atrib, &group, "na").filter(|n| !n.is_empty())
+                            .or_else(|| ucd_attr(&atrib, &group, "na1")).unwrap_or("");
+                        let name = name.replace('#', cp);
+                        d.add_text(prefixf, &word_prefixes(&name));
+                        d.add_text(namef, &name);
+                        d.add_text(gcf, ucd_attr(&atrib, &group, "gc").unwrap_or("Cn"));
+                        d.add_text(scf, ucd_attr(&atrib, &group, "sc").unwrap_or("Zzzz"));
+                        d.add_text(bcf, ucd_attr(&atrib, &group, "bc").unwrap_or("L"));
+                        d.add_text(gcbf, ucd_attr(&atrib, &group, "GCB").unwrap_or("XX"));
+                        d.add_text(epf, ucd_attr(&atrib, &group, "ExtPict").unwrap_or("N"));
+                        d.add_text(incbf, ucd_attr(&atrib, &group, "InCB").unwrap_or("None"));
+                        d.add_u64(cccf, ucd_attr(&atrib, &group, "ccc").unwrap_or("0").parse()?);
+                        d.add_text(dmf, &ucd_chars(ucd_attr(&atrib, &group, "dm").unwrap_or("#"), cp)?);
+                        d.add_text(dtf, ucd_attr(&atrib, &group, "dt").unwrap_or("none"));
+                        d.add_text(compexf, ucd_attr(&atrib, &group, "Comp_Ex").unwrap_or("N"));
+                        for &(attr, f) in &case_fields {
+                            d.add_text(f, &ucd_chars(ucd_attr(&atrib, &group, attr).unwrap_or("#"), cp)?);
+                        }
+                        d.add_text(agef, ucd_attr(&atrib, &group, "age").unwrap_or("unassigned"));
+                        doc = Some(d);
+                    },
+                    "block" => {
+                        let cp = |attr| ucd_attr(&atrib, &[], attr).and_then(|hex| u32::from_str_radix(hex, 16).ok());
+                        if let (Some(first), Some(last), Some(name)) = (cp("first-cp"), cp("last-cp"), ucd_attr(&atrib, &[], "name")) {
+                            blocks.push(Block { first, last, name: name.to_owned() });
+                        }
+                    },
+                    "name-alias" => {
+                        if let (Some(d), Some(alias)) = (doc.as_mut(), ucd_attr(&atrib, &[], "alias")) {
+                            d.add_text(aliasf, alias);
+                        }
+                    },
+                    _ => {}
+                }
+            },
+            Ok(XmlEvent::EndElement { ref name }) => match name.local_name.as_str() {
+                "char" => if let Some(d) = doc.take() { ixw.add_document(d); },
+                "group" => group.clear(),
+                _ => {}
+            },
+            Err(e) => { return Err(Box::new(e)); },
+            _ => {}
+        }
+    }
+    ixw.commit().map_err(TError)?;
+    Ok((blocks, ranges))
+}
+
+/// The character search index shared by every frontend
+pub struct Search {
+    namef: Field, blckf: Field, cpnf: Field, gcf: Field, aliasf: Field, scf: Field, bcf: Field,
+    gcbf: Field, epf: Field, incbf: Field, cccf: Field, dmf: Field, dtf: Field, compexf: Field,
+    ucf: Field, lcf: Field, tcf: Field, cff: Field, agef: Field,
+    reader: IndexReader,
+    qpar: QueryParser,
+    ranking: Ranking,
+    fuzzy: Fuzzy,
+    blocks: Vec<Block>,
+    ranges: Vec<Range>,
+    /// what finds the words one and two edits from another, slow enough to build that it is only done once
+    typos: [LevenshteinAutomatonBuilder; 2]
+}
+
+/// The most of the best matches by the index's own score that are put in order by the `Ranking`, the rest are left
+/// as they are
+const RERANK_WINDOW: usize = 500;
+/// How many matches past the end of a page are ranked too, so that ones the index scores a bit lower can still make it
+/// in, and pages fetched one after another only disagree about ones it scores far lower
+const RERANK_MARGIN: usize = 50;
+
+impl Search {
+    /// Open the index in `./index`, (re)building it from the UCD if it is missing or out of date, ranking results
+    /// and forgiving typos the way the configuration says
+    pub fn open(config: &Option<TomlValue>) -> Result<Search, Box<dyn Error>> {
+        let schema = schema();
+        match fs::OpenOptions::new().read(true).write(true).open("./index_version") {
+            Ok(mut f) => {
+                let mut s = String::new();
+                f.read_to_string(&mut s)?;
+                if s.parse::<u32>()? < INDEX_VERSION {
+                    match fs::remove_dir_all("./index") {
+                        Ok(()) => {},
+                        Err(e) => match e.kind() {
+                            IOErrorKind::NotFound => {},
+                            _ => return Err(Box::new(e))
+                        }
+                    }
+                    f.set_len(0)?;
+                    f.seek(SeekFrom::Start(0))?;
+                    write!(f, "{}", INDEX_VERSION)?;
+                }
+            },
+            Err(e) => match e.kind() {
+                IOErrorKind::NotFound => {
+                    let mut f = File::create("./index_version")?;
+                    write!(f, "{}", INDEX_VERSION)?;
+                    match fs::remove_dir_all("./index") {
+                        Ok(()) => {},
+                        Err(e) => match e.kind() {
+                            IOErrorKind::NotFound => {},
+                            _ => return Err(Box::new(e))
+                        }
+                    }
+                }
+                _ => return Err(Box::new(e))
+            }
+        };
+        let index = match tantivy::directory::MmapDirectory::open("./index").map_err(tantivy::TantivyError::from).map(Index::open) {
+            Ok(ix) => ix.map_err(TError)?,
+            Err(tantivy::TantivyError::PathDoesNotExist(_)) => {
+                fs::create_dir("./index")?;
+                let ix = Index::create(tantivy::directory::MmapDirectory::open("./index")?, schema).map_err(TError)?;
+                if let Err(e) = ucd_xml().and_then(|f| build_index(&ix, f))
+                    .and_then(|(blocks, ranges)| write_blocks(BLOCKS_PATH, &blocks).and_then(|()| write_ranges(RANGES_PATH, &ranges))) {
+                    fs::remove_dir_all("./index")?;
+                    return Err(e);
+                }
+                ix
+            },
+            Err(e) => {
+                return Err(Box::new(TError(e)));
+            }
+        };
+        let mut search = Search::from_index(index)?;
+        search.blocks = read_blocks(BLOCKS_PATH)?;
+        search.ranges = read_ranges(RANGES_PATH)?;
+        search.set_ranking(Ranking::from_config(config));
+        search.set_fuzzy(Fuzzy::from_config(config));
+        Ok(search)
+    }
+
+    /// Create a search over an already built index
+    pub fn from_index(index: Index) -> Result<Search, Box<dyn Error>> {
+        let schema = index.schema();
+        let namef = schema.get_field("name").ok_or("index is missing the name field")?;
+        let blckf = schema.get_field("blck").ok_or("index is missing the blck field")?;
+        let cpnf = schema.get_field("codepnt").ok_or("index is missing the codepnt field")?;
+        let gcf = schema.get_field("gc").ok_or("index is missing the gc field")?;
+        let aliasf = schema.get_field("alias").ok_or("index is missing the alias field")?;
+        let scf = schema.get_field("sc").ok_or("index is missing the sc field")?;
+        let bcf = schema.get_field("bc").ok_or("index is missing the bc field")?;
+        let gcbf = schema.get_field("gcb").ok_or("index is missing the gcb field")?;
+        let epf = schema.get_field("extpict").ok_or("index is missing the extpict field")?;
+        let incbf = schema.get_field("incb").ok_or("index is missing the incb field")?;
+        let cccf = schema.get_field("ccc").ok_or("index is missing the ccc field")?;
+        let dmf = schema.get_field("dm").ok_or("index is missing the dm field")?;
+        let dtf = schema.get_field("dt").ok_or("index is missing the dt field")?;
+        let compexf = schema.get_field("compex").ok_or("index is missing the compex field")?;
+        let ucf = schema.get_field("uc").ok_or("index is missing the uc field")?;
+        let lcf = schema.get_field("lc").ok_or("index is missing the lc field")?;
+        let tcf = schema.get_field("tc").ok_or("index is missing the tc field")?;
+        let cff = schema.get_field("cf").ok_or("index is missing the cf field")?;
+        let agef = schema.get_field("age").ok_or("index is missing the age field")?;
+        let reader = index.reader().map_err(TError)?;
+        let mut search = Search {
+            namef, blckf, cpnf, gcf, aliasf, scf, bcf, gcbf, epf, incbf, cccf, dmf, dtf, compexf, ucf, lcf, tcf, cff, agef, reader,
+            qpar: QueryParser::new(schema, vec![namef, blckf, aliasf], ::tantivy::tokenizer::TokenizerManager::default()),
+            ranking: Ranking::default(),
+            fuzzy: Fuzzy::default(),
+            blocks: Vec::new(),
+            ranges: Vec::new(),
+            typos: [LevenshteinAutomatonBuilder::new(1, true), LevenshteinAutomatonBuilder::new(2, true)]
+        };
+        search.set_ranking(Ranking::default());
+        Ok(search)
+    }
+
+    /// Change how results are ordered, usually to the one from the configuration
+    pub fn set_ranking(&mut self, ranking: Ranking) {
+        self.qpar.set_field_boost(self.namef, ranking.name_boost);
+        self.qpar.set_field_boost(self.aliasf, ranking.name_boost);
+        self.ranking = ranking;
+    }
+
+    /// Change how forgiving of typos the search is
+    pub fn set_fuzzy(&mut self, fuzzy: Fuzzy) {
+        self.fuzzy = fuzzy;
+    }
+
+    fn char_info(&self, doc: &Document) -> CharInfo {
+        CharInfo {
+            cp: doc.get_first(self.cpnf).map_or(0, Value::u64_value) as u32,
+            name: doc.get_first(self.namef).and_then(Value::text).unwrap_or("!").to_owned(),
+            block: doc.get_first(self.blckf).and_then(Value::text).unwrap_or("!").to_owned(),
+            category: doc.get_first(self.gcf).and_then(Value::text).unwrap_or("Cn").to_owned(),
+            aliases: doc.get_all(self.aliasf).iter().filter_map(|v| v.text()).map(str::to_owned).collect(),
+            script: doc.get_first(self.scf).and_then(Value::text).unwrap_or("Zzzz").to_owned(),
+            bidi_class: doc.get_first(self.bcf).and_then(Value::text).unwrap_or("L").to_owned(),
+            grapheme_break: doc.get_first(self.gcbf).and_then(Value::text).unwrap_or("XX").to_owned(),
+            extended_pictographic: doc.get_first(self.epf).and_then(Value::text) == Some("Y"),
+            indic_conjunct_break: doc.get_first(self.incbf).and_then(Value::text).unwrap_or("None").to_owned(),
+            combining_class: doc.get_first(self.cccf).map_or(0, Value::u64_value) as u8,
+            decomposition: doc.get_first(self.dmf).and_then(Value::text).unwrap_or("").to_owned(),
+            decomposition_type: doc.get_first(self.dtf).and_then(Value::text).unwrap_or("none").to_owned(),
+            uppercase: doc.get_first(self.ucf).and_then(Value::text).unwrap_or("").to_owned(),
+            lowercase: doc.get_first(self.lcf).and_then(Value::text).unwrap_or("").to_owned(),
+            titlecase: doc.get_first(self.tcf).and_then(Value::text).unwrap_or("").to_owned(),
+            casefold: doc.get_first(self.cff).and_then(Value::text).unwrap_or("").to_owned(),
+            age: doc.get_first(self.agef).and_then(Value::text).unwrap_or("unassigned").to_owned()
+        }
+    }
+
+    /// Run a query in Tantivy's query language, returning at most `limit` of the best matches
+    pub fn query(&self, query: &str, limit: usize) -> Result<Vec<CharInfo>, Box<dyn Error>> {
+        self.query_page(query, 0, limit).map(|page| page.chars)
+    }
+
+    /// Like `query`, but skipping the `offset` best matches and counting all of them, to fetch more as they're needed.
+    /// When there are only a few matches, the ones with typos in the query corrected are added after them.
+    pub fn query_page(&self, query: &str, offset: usize, limit: usize) -> Result<Page, Box<dyn Error>> {
+        let prefixed = with_prefix(query);
+        let parsed = prefixed.trim();
+        // the error is worked out on what Tantivy saw, and pointed at what was typed
+        let lead = prefixed[..prefixed.len() - prefixed.trim_start().len()].chars().count();
+        let q = self.qpar.parse_query(parsed).map_err(|e| {
+            let mut error = QError::new(parsed, e);
+            error.position = error.position.map(|p| typed_position(query, p + lead));
+            error
+        })?;
+        let words = query_words(query);
+        let page = self.ranked_page(&*q, &words, offset, limit)?;
+        let fuzzy_query = match self.fuzzy.query(self.namef, query) {
+            Some(fq) if self.fuzzy.wanted(page.total) => fq,
+            _ => return Ok(page)
+        };
+        // so few match that they all fit in the first page
+        let mut chars = self.ranked_page(&*q, &words, 0, page.total)?.chars;
+        let strict = chars.len();
+        let fuzzy = self.ranked_page(&fuzzy_query, &words, 0, RERANK_WINDOW)?;
+        chars.extend(fuzzy.chars.into_iter().filter(|f| !chars[..strict].iter().any(|c| c.cp == f.cp)).collect::<Vec<_>>());
+        // the typos past the window aren't listed, so they aren't counted either
+        let total = chars.len();
+        Ok(Page { chars: chars.into_iter().skip(offset).take(limit).collect(), total })
+    }
+
+    /// A page of the matches of `q`, the best of them by the index's score put in order by the `Ranking`
+    fn ranked_page(&self, q: &dyn Query, words: &[String], offset: usize, limit: usize) -> Result<Page, Box<dyn Error>> {
+        let s = self.reader.searcher();
+        // only the matches that could end up on the page are worth loading to rank
+        let window = (offset + limit + RERANK_MARGIN).min(RERANK_WINDOW);
+        // Tantivy has no offset, so the ones before the page are collected and dropped
+        let collector = (Count, TopDocs::with_limit(window.max(offset + limit).max(1)));
+        let (total, results) = s.search(q, &collector).map_err(TError)?;
+        let load = |da| s.doc(da).map(|d| self.char_info(&d)).map_err(|e| Box::new(TError(e)) as Box<dyn Error>);
+        let mut ranked = results.iter().take(window)
+            .map(|&(score, da)| load(da).map(|info| (self.ranking.score(score, &info, words), info)))
+            .collect::<Result<Vec<_>, _>>()?;
+        // the sort is stable, so ties keep the index's order
+        ranked.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(::std::cmp::Ordering::Equal));
+        let mut chars = ranked.into_iter().map(|(_, info)| info).skip(offset).take(limit).collect::<Vec<_>>();
+        for &(_, da) in results.iter().skip(window.max(offset)).take(limit - chars.len()) {
+            chars.push(load(da)?);
+        }
+        Ok(Page { chars, total })
+    }
+
+    /// Find the character with codepoint `cp`
+    pub fn lookup(&self, cp: u32) -> Result<Option<CharInfo>, Box<dyn Error>> {
+        let s = self.reader.searcher();
+        let q = TermQuery::new(Term::from_field_u64(self.cpnf, cp as u64), IndexRecordOption::Basic);
+        let results = s.search(&q, &TopDocs::with_limit(1)).map_err(TError)?;
+        match results.first() {
+            Some((_, da)) => Ok(Some(self.char_info(&s.doc(*da).map_err(TError)?))),
+            None => Ok(None)
+        }
+    }
+
+    /// The primary composite of `first` and `second`, the character NFC puts in place of the pair
+    pub fn compose(&self, first: char, second: char) -> Result<Option<char>, Box<dyn Error>> {
+        let s = self.reader.searcher();
+        let pair: String = [first, second].iter().collect();
+        let term = |f, text: &str| Box::new(TermQuery::new(Term::from_field_text(f, text), IndexRecordOption::Basic)) as Box<dyn Query>;
+        let q = BooleanQuery::from(vec![
+            (Occur::Must, term(self.dmf, &pair)),
+            (Occur::Must, term(self.dtf, "can")),
+            // singletons and some other decompositions are never recomposed
+            (Occur::MustNot, term(self.compexf, "Y"))
+        ]);
+        let results = s.search(&q, &TopDocs::with_limit(1)).map_err(TError)?;
+        match results.first() {
+            Some((_, da)) => Ok(self.char_info(&s.doc(*da).map_err(TError)?).chr()),
+            None => Ok(None)
+        }
+    }
+
+    /// The words of names, blocks and aliases that start with `prefix`, in order, with how many characters have each.
+    /// The words are lowercased, like the index has them.
+    pub fn vocabulary(&self, prefix: &str) -> Result<Vec<(String, u32)>, Box<dyn Error>> {
+        let prefix = prefix.to_lowercase();
+        let mut words = BTreeMap::new();
+        for sr in self.reader.searcher().segment_readers() {
+            for &field in &[self.namef, self.blckf, self.aliasf] {
+                let index = sr.inverted_index(field);
+                let mut terms = index.terms().range().ge(prefix.as_bytes()).into_stream();
+                while terms.advance() {
+                    if !terms.key().starts_with(prefix.as_bytes()) { break; }
+                    let word = String::from_utf8_lossy(terms.key()).into_owned();
+                    *words.entry(word).or_insert(0) += terms.value().doc_freq;
+                }
+            }
+        }
+        Ok(words.into_iter().collect())
+    }
+
+    /// How many characters have `word` in their name, block or aliases, counted like `vocabulary` does
+    pub fn word_count(&self, word: &str) -> u32 {
+        let word = word.to_lowercase();
+        let mut count = 0;
+        for sr in self.reader.searcher().segment_readers() {
+            for &field in &[self.namef, self.blckf, self.aliasf] {
+                count += sr.inverted_index(field).terms().get(word.as_bytes()).map_or(0, |info| info.doc_freq);
+            }
+        }
+        count
+    }
+
+    /// The words of names, blocks and aliases other than `word` that are at most `distance` insertions, deletions,
+    /// substitutions or swaps of neighbouring characters away from it, in order, with how many characters have each.
+    /// There are none for a distance of 0, and at most 2 is looked at.
+    pub fn close_words(&self, word: &str, distance: u8) -> Result<Vec<(String, u32)>, Box<dyn Error>> {
+        let word = word.to_lowercase();
+        let builder = match distance { 0 => return Ok(Vec::new()), 1 => &self.typos[0], _ => &self.typos[1] };
+        let mut words = BTreeMap::new();
+        for sr in self.reader.searcher().segment_readers() {
+            for &field in &[self.namef, self.blckf, self.aliasf] {
+                let index = sr.inverted_index(field);
+                let mut terms = index.terms().search(builder.build_dfa(&word)).into_stream();
+                while terms.advance() {
+                    if terms.key() == word.as_bytes() { continue; }
+                    let close = String::from_utf8_lossy(terms.key()).into_owned();
+                    *words.entry(close).or_insert(0) += terms.value().doc_freq;
+                }
+            }
+        }
+        Ok(words.into_iter().collect())
+    }
+
+    /// Every block, in codepoint order
+    pub fn blocks(&self) -> &[Block] {
+        &self.blocks
+    }
+
+    /// The characters that are only in the UCD as part of a range, not one by one
+    pub fn ranges(&self) -> &[Range] {
+        &self.ranges
+    }
+
+    /// The characters from `first` to `last`, in codepoint order
+    pub fn range(&self, first: u32, last: u32) -> Result<Vec<CharInfo>, Box<dyn Error>> {
+        let q = RangeQuery::new_u64(self.cpnf, first as u64..last as u64 + 1);
+        self.all_matching(&q)
+    }
+
+    /// The characters of a script, given as an ISO 15924 code like `Cyrl`, in codepoint order
+    pub fn script(&self, sc: &str) -> Result<Vec<CharInfo>, Box<dyn Error>> {
+        self.all_matching(&TermQuery::new(Term::from_field_text(self.scf, sc), IndexRecordOption::Basic))
+    }
+
+    /// The characters of a general category like `Sm`, in codepoint order
+    pub fn category(&self, gc: &str) -> Result<Vec<CharInfo>, Box<dyn Error>> {
+        self.all_matching(&TermQuery::new(Term::from_field_text(self.gcf, gc), IndexRecordOption::Basic))
+    }
+
+    fn all_matching(&self, q: &dyn Query) -> Result<Vec<CharInfo>, Box<dyn Error>> {
+        let s = self.reader.searcher();
+        let results = s.search(q, &TopDocs::with_limit(s.num_docs().max(1) as usize)).map_err(TError)?;
+        let mut chars = results.iter()
+            .map(|(_, da)| s.doc(*da).map(|d| self.char_info(&d)).map_err(|e| Box::new(TError(e)) as Box<dyn Error>))
+            .collect::<Result<Vec<_>, _>>()?;
+        chars.sort_by_key(|c| c.cp);
+        Ok(chars)
+    }
+
+    /// Every character in the index, in codepoint order
+    pub fn all_chars(&self) -> Result<Vec<CharInfo>, Box<dyn Error>> {
+        let s = self.reader.searcher();
+        let mut chars = Vec::with_capacity(s.num_docs() as usize);
+        for sr in s.segment_readers() {
+            let store = sr.get_store_reader();
+            for doc in sr.doc_ids_alive() {
+                chars.push(self.char_info(&store.get(doc).map_err(TError)?));
+            }
+        }
+        chars.sort_by_key(|c| c.cp);
+        Ok(chars)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use ::search::*;
+
+    /// Index UCD XML in memory
+    pub fn search_from_xml(xml: &str) -> Search {
+        let index = Index::create_in_ram(schema());
+        let (blocks, ranges) = build_index(&index, xml.as_bytes()).expect("building fixture index");
+        let mut s = Search::from_index(index).expect("opening fixture index");
+        s.blocks = blocks;
+        s.ranges = ranges;
+        s.reader.reload().expect("reloading reader");
+        s
+    }
+
+    /// The characters every fixture has: two capitals, a Greek letter and two arrows
+    const BASE: &str = r#"<group blk="ASCII" gc="Lu" sc="Latn" bc="L" age="1.1">
+<char cp="0041" na="LATIN CAPITAL LETTER A" lc="0061" cf="0061"/>
+<char cp="0042" na="LATIN CAPITAL LETTER B" lc="0062" cf="0062"/>
+</group>
+<group blk="Greek" gc="Ll" sc="Grek" bc="L">
+<char cp="03BB" na="GREEK SMALL LETTER LAMDA"/>
+</group>
+<group blk="Arrows" gc="Sm" sc="Zyyy" bc="ON">
+<char cp="2190" na="LEFTWARDS ARROW"/>
+<char cp="2192" na="RIGHTWARDS ARROW"/>
+</group>
+"#;
+
+    const BLOCKS: &str = r#"<blocks>
+<block first-cp="0000" last-cp="007F" name="Basic Latin"/>
+<block first-cp="0080" last-cp="00FF" name="Latin-1 Supplement"/>
+<block first-cp="2190" last-cp="21FF" name="Arrows"/>
+</blocks>
+"#;
+
+    /// Index the shared characters plus the `<group>`s a test needs besides them
+    pub fn fixture(groups: &str) -> Search {
+        search_from_xml(&format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<ucd xmlns=\"http://www.unicode.org/ns/2003/ucd/1.0\">\n\
+                                  <repertoire>\n{}{}</repertoire>\n{}</ucd>\n", BASE, groups, BLOCKS))
+    }
+
+    const CHARS: &str = r#"<group blk="ASCII" gc="Ll" sc="Latn" bc="L">
+<char cp="0065" na="LATIN SMALL LETTER E"/>
+</group>
+<group blk="Latin_1_Sup" gc="Zs" sc="Zyyy" bc="CS">
+<char cp="00A0" na="NO-BREAK SPACE"><name-alias alias="NBSP" type="abbreviation"/></char>
+</group>
+<group blk="Latin_1_Sup" gc="Ll" sc="Latn" bc="L">
+<char cp="00DF" na="LATIN SMALL LETTER SHARP S"/>
+<char cp="00E9" na="LATIN SMALL LETTER E WITH ACUTE"/>
+</group>
+<group blk="Cyrillic" gc="Ll" sc="Cyrl" bc="L">
+<char cp="0430" na="CYRILLIC SMALL LETTER A"/>
+</group>
+<group blk="Punctuation" gc="Cf" sc="Zyyy">
+<char cp="202E" na="RIGHT-TO-LEFT OVERRIDE" bc="RLO"><name-alias alias="RLO" type="abbreviation"/></char>
+</group>
+"#;
+
+    #[test]
+    fn data_files_appear_whole() {
+        let path = ::std::env::temp_dir().join(format!("ununi-data-test-{}", ::std::process::id()));
+        let path = path.to_str().unwrap();
+        let failed = write_whole(path, |f| { f.write_all(b"half")?; Err("connection reset".into()) });
+        assert_eq!(failed.unwrap_err().to_string(), "connection reset");
+        assert!(File::open(path).is_err());
+        assert!(File::open(format!("{}.part", path)).is_err());
+
+        let mut f = write_whole(path, |f| Ok(f.write_all(b"whole")?)).unwrap();
+        let mut text = String::new();
+        f.read_to_string(&mut text).unwrap();
+        assert_eq!(text, "whole");
+        ::std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn builds_index() {
+        let s = search_from_xml(r##"<?xml version="1.0" encoding="UTF-8"?>
+<ucd xmlns="http://www.unicode.org/ns/2003/ucd/1.0">
+<repertoire>
+<group blk="ASCII" gc="Cc" sc="Zyyy" bc="BN">
+<char cp="000A" na="" na1="LINE FEED (LF)" bc="B"><name-alias alias="LF" type="abbreviation"/><name-alias alias="NEW LINE" type="control"/></char>
+<char cp="0041" na="LATIN CAPITAL LETTER A" gc="Lu" sc="Latn" bc="L" lc="0061" uc="#"/>
+</group>
+<group blk="Devanagari" gc="Lo" sc="Deva" bc="L" InCB="Consonant">
+<char cp="0915" na="DEVANAGARI LETTER KA"/>
+</group>
+<group blk="CJK_Compat_Ideographs" gc="Lo" sc="Hani" bc="L">
+<char cp="F900" na="CJK COMPATIBILITY IDEOGRAPH-#" dt="can" dm="8C48"/>
+</group>
+<group blk="Hangul" gc="Lo" sc="Hang" bc="L">
+<char first-cp="AC00" last-cp="D7A3" na=""/>
+</group>
+</repertoire>
+<blocks>
+<block first-cp="0000" last-cp="007F" name="Basic Latin"/>
+<block first-cp="F900" last-cp="FAFF" name="CJK Compatibility Ideographs"/>
+</blocks>
+</ucd>
+"##);
+        // ranges aren't in the index, their names are worked out instead
+        assert_eq!(s.all_chars().unwrap().iter().map(|c| c.cp).collect::<Vec<_>>(), vec![0xa, 0x41, 0x915, 0xf900]);
+        let lf = s.lookup(0xa).unwrap().unwrap();
+        assert_eq!((lf.name.as_str(), lf.category.as_str(), lf.bidi_class.as_str()), ("LINE FEED (LF)", "Cc", "B"));
+        assert_eq!(lf.aliases, vec!["LF", "NEW LINE"]);
+        let a = s.lookup(0x41).unwrap().unwrap();
+        assert_eq!((a.lowercase.as_str(), a.uppercase.as_str(), a.script.as_str()), ("a", "", "Latn"));
+        assert_eq!(a.indic_conjunct_break, "None");
+        assert_eq!(s.lookup(0x915).unwrap().unwrap().indic_conjunct_break, "Consonant");
+        let cjk = s.lookup(0xf900).unwrap().unwrap();
+        assert_eq!((cjk.name.as_str(), cjk.decomposition.as_str()), ("CJK COMPATIBILITY IDEOGRAPH-F900", "\u{8c48}"));
+        assert_eq!(s.blocks().len(), 2);
+        assert_eq!(s.query("line feed", 1).unwrap()[0].cp, 0xa);
+    }
+
+    #[test]
+    fn finds_characters_by_name() {
+        let s = fixture(CHARS);
+        let res = s.query("lamda", 10).expect("searching");
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].cp, 0x3bb);
+        assert_eq!(res[0].name, "GREEK SMALL LETTER LAMDA");
+        assert_eq!(res[0].block, "Greek");
+    }
+
+    #[test]
+    fn finds_characters_by_block() {
+        let s = fixture(CHARS);
+        let mut res = s.query("arrows", 10).expect("searching").iter().map(|c| c.cp).collect::<Vec<_>>();
+        res.sort();
+        assert_eq!(res, vec![0x2190, 0x2192]);
+    }
+
+    #[test]
+    fn finds_characters_by_alias() {
+        let s = fixture(CHARS);
+        let res = s.query("nbsp", 10).expect("searching");
+        assert_eq!(res[0].cp, 0xa0);
+        assert_eq!(res[0].aliases, vec![String::from("NBSP")]);
+        assert_eq!(res[0].short_name(), "NBSP");
+        assert!(res[0].is_invisible() && !res[0].is_bidi_control());
+        assert_eq!(res[0].describe(), "U+00A0 NO-BREAK SPACE (NBSP)");
+        let rlo = s.lookup(0x202e).unwrap().unwrap();
+        assert!(rlo.is_invisible() && rlo.is_bidi_control());
+        assert_eq!(s.lookup(0x430).unwrap().unwrap().script, "Cyrl");
+    }
+
+    #[test]
+    fn lookup_by_codepoint() {
+        let s = fixture(CHARS);
+        assert_eq!(s.lookup(0x2192).unwrap().map(|c| c.name), Some(String::from("RIGHTWARDS ARROW")));
+        assert_eq!(s.lookup(0x2191).unwrap(), None);
+    }
+
+    #[test]
+    fn ranges_scripts_and_categories() {
+        let s = fixture(CHARS);
+        assert_eq!(s.blocks()[2], Block { first: 0x2190, last: 0x21ff, name: String::from("Arrows") });
+        let cps = |v: Vec<CharInfo>| v.iter().map(|c| c.cp).collect::<Vec<_>>();
+        assert_eq!(cps(s.range(0x80, 0xff).unwrap()), vec![0xa0, 0xdf, 0xe9]);
+        assert_eq!(cps(s.script("Cyrl").unwrap()), vec![0x430]);
+        assert_eq!(cps(s.category("Sm").unwrap()), vec![0x2190, 0x2192]);
+    }
+
+    #[test]
+    fn pages() {
+        let s = fixture(CHARS);
+        let cps = |v: Vec<CharInfo>| v.iter().map(|c| c.cp).collect::<Vec<_>>();
+        let all = s.query_page("letter", 0, 100).unwrap();
+        assert!(all.total > 4);
+        assert_eq!(all.chars.len(), all.total);
+        let first = s.query_page("letter", 0, 2).unwrap();
+        let second = s.query_page("letter", 2, 2).unwrap();
+        assert_eq!(first.total, all.total);
+        assert_eq!(cps(first.chars).into_iter().chain(cps(second.chars)).collect::<Vec<_>>(), cps(all.chars)[..4].to_vec());
+        assert!(s.query_page("letter", all.total, 10).unwrap().chars.is_empty());
+        assert_eq!(s.query_page("letter", 0, 0).unwrap().chars.len(), 0);
+    }
+
+    #[test]
+    fn prefix_of_last_word() {
+        assert_eq!(word_prefixes("NO-BREAK SPACE"), "N B BR BRE BREA S SP SPA SPAC");
+        assert_eq!(with_prefix("leftwards arr"), "leftwards (arr OR nameprefix:arr)");
+        assert_eq!(with_prefix("arrow "), "arrow ");
+        assert_eq!(with_prefix("blck:arr"), "blck:arr");
+        // whitespace that takes more than a byte
+        assert_eq!(with_prefix("leftwards\u{3000}arr"), "leftwards\u{3000}(arr OR nameprefix:arr)");
+        assert_eq!(with_prefix("arrow\u{a0}"), "arrow\u{a0}");
+        assert_eq!(typed_position("left\u{a0}arr", 6), 5);
+        let s = fixture(CHARS);
+        let mut arr = s.query("arr", 10).unwrap().iter().map(|c| c.cp).collect::<Vec<_>>();
+        arr.sort();
+        assert_eq!(arr, vec![0x2190, 0x2192]);
+        assert_eq!(s.query("leftwards arr", 10).unwrap()[0].cp, 0x2190);
+        // the finished word still comes before longer ones starting with it
+        assert_eq!(s.query("small letter e", 10).unwrap()[0].cp, 0x65);
+        // only the last word is a prefix
+        assert!(s.query("arr ", 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn explains_parse_errors() {
+        let s = fixture(CHARS);
+        let error = |q: &str| match s.query_page(q, 0, 10) {
+            Ok(_) => panic!("{} parsed", q),
+            Err(e) => { let qe = e.downcast::<QError>().unwrap(); (qe.message.clone(), qe.position) }
+        };
+        assert_eq!(error("\"left arrow"), ("a quote that isn't closed".to_owned(), Some(0)));
+        assert_eq!(error("left (arrow").1, Some(5));
+        assert_eq!(error("left arrow)").1, Some(10));
+        assert_eq!(error("blck: arrow"), ("nothing to search for after the `:`".to_owned(), Some(4)));
+        assert_eq!(error("arrow :left").1, Some(6));
+        assert_eq!(error("a::b").1, Some(2));
+        assert_eq!(error("λ foo:arrow"), ("there is no field named `foo`".to_owned(), Some(2)));
+        assert_eq!(error("codepnt:x").1, Some(0));
+        assert_eq!(error("-arrow").1, Some(0));
+        assert_eq!(s.query_page("name:", 0, 10).err().unwrap().to_string(), "nothing to search for after the `:` at character 5");
+        // leading spaces are trimmed before parsing, and the last word searched for as a prefix too
+        assert_eq!(error("  \"left arrow").1, Some(2));
+        assert_eq!(error(" (left arrow").1, Some(1));
+        assert_eq!((typed_position("leftwards arr", 9), typed_position("leftwards arr", 11)), (9, 10));
+        assert_eq!((typed_position("leftwards arr", 29), typed_position("leftwards arr", 33)), (10, 13));
+        // Tantivy rejects more than is looked for, which is still explained, only not where
+        assert_eq!(error("arrow AND"), ("the query can't be parsed".to_owned(), None));
+    }
+
+    #[test]
+    fn lenient_fallback() {
+        let s = fixture(CHARS);
+        assert_eq!(literal_query("\"leftwards arrow OR blck:(arrows"), "leftwards arrow or blck arrows");
+        assert_eq!(literal_query("AND (:"), "and");
+        for q in &["\"leftwards arrow", "leftwards: arrow)", "a::b leftwards", "foo:leftwards"] {
+            assert!(s.query(q, 10).is_err());
+            assert_eq!(s.query(&literal_query(q), 10).unwrap()[0].cp, 0x2190);
+        }
+    }
+
+    #[test]
+    fn bad_query_is_an_error() {
+        let s = fixture(CHARS);
+        assert!(s.query("name:", 10).is_err());
+    }
+}
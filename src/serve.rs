@@ -0,0 +1,354 @@
+use std::error::Error;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream, SocketAddr};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use serde_json::Value as JsonValue;
+use toml::Value as TomlValue;
+
+use search::{Search, Block, QError, literal_query};
+use daemon::char_json;
+
+const INDEX_HTML: &str = include_str!("serve.html");
+
+/// The most results `/search` returns, so no request can make the search allocate without bound
+const MAX_LIMIT: usize = 1000;
+/// How many connections are answered at once, more are turned away until some are done
+const MAX_CONNECTIONS: usize = 64;
+/// How long a client has to send its request, or take the response, before it is hung up on
+const TIMEOUT: Duration = Duration::from_secs(10);
+/// The longest request line or header read, in bytes, so a client can't make a line grow without bound
+const MAX_LINE: u64 = 8192;
+/// The most headers read after the request line
+const MAX_HEADERS: usize = 100;
+
+/// Decode the `%XX` escapes in part of a URL
+fn percent_decode(s: &str) -> String {
+    let b = s.as_bytes();
+    let mut bytes = Vec::with_capacity(b.len());
+    let mut i = 0;
+    while i < b.len() {
+        match b[i] {
+            b'%' if i + 2 < b.len() && s.is_char_boundary(i + 3) => match u8::from_str_radix(&s[i+1..i+3], 16) {
+                Ok(v) => { bytes.push(v); i += 2; },
+                Err(_) => bytes.push(b'%')
+            },
+            c => bytes.push(c)
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Decode one `application/x-www-form-urlencoded` component of a query string
+fn url_decode(s: &str) -> String {
+    percent_decode(&s.replace('+', " "))
+}
+
+/// Find the value of `key` in a query string like `q=arrow&limit=5`
+fn query_param(query: &str, key: &str) -> Option<String> {
+    query.split('&')
+        .filter_map(|kv| { let mut kv = kv.splitn(2, '='); Some((kv.next()?, kv.next().unwrap_or(""))) })
+        .find(|&(k, _)| url_decode(k) == key)
+        .map(|(_, v)| url_decode(v))
+}
+
+/// Parse a codepoint written as `2192`, `U+2192` or `0x2192`
+fn parse_codepoint(s: &str) -> Option<u32> {
+    let hex = s.trim_start_matches("U+").trim_start_matches("u+").trim_start_matches("0x");
+    u32::from_str_radix(hex, 16).ok()
+}
+
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: String
+}
+
+impl Response {
+    fn json(v: JsonValue) -> Response {
+        Response { status: "200 OK", content_type: "application/json; charset=utf-8", body: v.to_string() }
+    }
+
+    fn error(status: &'static str, message: &str) -> Response {
+        Response { status, content_type: "application/json; charset=utf-8", body: json!({ "error": message }).to_string() }
+    }
+
+    fn write_to<W: Write>(&self, mut w: W) -> Result<(), Box<dyn Error>> {
+        write!(w, "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+               self.status, self.content_type, self.body.len(), self.body)?;
+        w.flush()?;
+        Ok(())
+    }
+}
+
+/// One of the connections being answered, given back when it is dropped, even if answering it panicked
+struct Slot(Arc<AtomicUsize>);
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// A block of the UCD and how many of its characters the index has one by one
+struct BlockSummary {
+    block: Block,
+    count: usize
+}
+
+/// Answers HTTP requests for character data
+pub struct Server {
+    search: Search,
+    blocks: Vec<BlockSummary>
+}
+
+impl Server {
+    pub fn new(search: Search) -> Result<Server, Box<dyn Error>> {
+        let mut blocks = search.blocks().iter().map(|b| BlockSummary { block: b.clone(), count: 0 }).collect::<Vec<_>>();
+        for c in search.all_chars()? {
+            // the blocks are in codepoint order and don't overlap
+            let i = blocks.partition_point(|b| b.block.last < c.cp);
+            if let Some(b) = blocks.get_mut(i).filter(|b| b.block.first <= c.cp) {
+                b.count += 1;
+            }
+        }
+        Ok(Server { search, blocks })
+    }
+
+    fn route(&self, method: &str, target: &str) -> Response {
+        if method != "GET" {
+            return Response::error("405 Method Not Allowed", "only GET is supported");
+        }
+        let mut target = target.splitn(2, '?');
+        let path = target.next().unwrap_or("/");
+        let query = target.next().unwrap_or("");
+        match path {
+            "/" | "/index.html" => Response { status: "200 OK", content_type: "text/html; charset=utf-8", body: INDEX_HTML.to_owned() },
+            "/search" => {
+                let q = query_param(query, "q").unwrap_or_default();
+                let limit = match query_param(query, "limit").map(|l| l.parse::<usize>()) {
+                    None => 40,
+                    Some(Ok(limit)) if limit <= MAX_LIMIT => limit,
+                    _ => return Response::error("400 Bad Request", &format!("expected a limit from 0 to {}", MAX_LIMIT))
+                };
+                match self.search.query(&q, limit) {
+                    Ok(results) => Response::json(JsonValue::Array(results.iter().map(char_json).collect())),
+                    // with where the query is wrong and its words to search for instead
+                    Err(e) => match e.downcast_ref::<QError>() {
+                        Some(qe) => Response { status: "400 Bad Request", content_type: "application/json; charset=utf-8",
+                                               body: json!({ "error": qe.message, "position": qe.position, "literal": literal_query(&q) }).to_string() },
+                        None => Response::error("400 Bad Request", &e.to_string())
+                    }
+                }
+            },
+            "/blocks" => Response::json(JsonValue::Array(self.blocks.iter()
+                .map(|b| json!({ "name": b.block.name, "first": b.block.first, "last": b.block.last, "count": b.count })).collect())),
+            _ if path.starts_with("/char/") => match parse_codepoint(&percent_decode(&path["/char/".len()..])) {
+                Some(cp) => match self.search.lookup(cp) {
+                    Ok(Some(c)) => Response::json(char_json(&c)),
+                    Ok(None) => Response::error("404 Not Found", "no such character"),
+                    Err(e) => Response::error("500 Internal Server Error", &e.to_string())
+                },
+                None => Response::error("400 Bad Request", "expected a hexadecimal codepoint")
+            },
+            _ => Response::error("404 Not Found", "no such endpoint")
+        }
+    }
+
+    fn handle(&self, stream: TcpStream) -> Result<(), Box<dyn Error>> {
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let resp = match read_head(&mut reader)? {
+            Ok(request_line) => {
+                let mut parts = request_line.split_whitespace();
+                match (parts.next(), parts.next()) {
+                    (Some(method), Some(target)) => self.route(method, target),
+                    _ => Response::error("400 Bad Request", "malformed request line")
+                }
+            },
+            Err(resp) => resp
+        };
+        resp.write_to(stream)
+    }
+}
+
+/// Read a line of at most `MAX_LINE` bytes into `line`, false if it was longer than that
+fn read_line<R: BufRead>(reader: &mut R, line: &mut String) -> io::Result<bool> {
+    let n = reader.by_ref().take(MAX_LINE).read_line(line)?;
+    Ok((n as u64) < MAX_LINE || line.ends_with('\n'))
+}
+
+/// Read the request line and the headers after it, which don't change anything we do but have to be read before
+/// responding. A request past the limits on their length and number is answered with the error to send instead.
+fn read_head<R: BufRead>(reader: &mut R) -> io::Result<Result<String, Response>> {
+    let mut request_line = String::new();
+    if !read_line(reader, &mut request_line)? {
+        return Ok(Err(Response::error("400 Bad Request", "request line too long")));
+    }
+    let mut header = String::new();
+    for _ in 0..=MAX_HEADERS {
+        header.clear();
+        if !read_line(reader, &mut header)? {
+            return Ok(Err(Response::error("431 Request Header Fields Too Large", "header too long")));
+        }
+        if header.trim().is_empty() {
+            return Ok(Ok(request_line));
+        }
+    }
+    Ok(Err(Response::error("431 Request Header Fields Too Large", "too many headers")))
+}
+
+/// Accept connections on `listener` forever, each on its own thread, answering at most `max_connections` at once
+fn listen(server: Arc<Server>, listener: TcpListener, max_connections: usize) -> Result<(), Box<dyn Error>> {
+    let open = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming() {
+        // one connection failing, like a client hanging up before it is accepted, doesn't stop the others
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => { eprintln!("accepting a connection failed: {}", e); continue; }
+        };
+        if let Err(e) = stream.set_read_timeout(Some(TIMEOUT)).and_then(|_| stream.set_write_timeout(Some(TIMEOUT))) {
+            eprintln!("setting timeouts on a connection failed: {}", e);
+            continue;
+        }
+        // taken before checking, so turning the connection away gives it back too
+        let slot = Slot(open.clone());
+        if open.fetch_add(1, Ordering::SeqCst) >= max_connections {
+            let _ = Response::error("503 Service Unavailable", "too many connections, try again later").write_to(stream);
+            continue;
+        }
+        let server = server.clone();
+        thread::spawn(move || { let _ = server.handle(stream); drop(slot); });
+    }
+    Ok(())
+}
+
+/// `ununi serve [--port PORT] [--bind ADDRESS]` serves character data over HTTP, on 127.0.0.1:7323 by default
+pub fn run(config: &Option<TomlValue>, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut port = 7323u16;
+    let mut bind = String::from("127.0.0.1");
+    let mut args = args.iter();
+    while let Some(a) = args.next() {
+        match a.as_str() {
+            "--port" => port = args.next().ok_or("--port needs a port number")?.parse()?,
+            "--bind" => bind = args.next().ok_or("--bind needs an address")?.clone(),
+            _ => return Err(format!("unknown argument {}; usage: ununi serve [--port PORT] [--bind ADDRESS]", a).into())
+        }
+    }
+    let addr = SocketAddr::new(bind.parse()?, port);
+    let listener = TcpListener::bind(addr)?;
+    eprintln!("serving on http://{}", listener.local_addr()?);
+    listen(Arc::new(Server::new(Search::open(config)?)?), listener, MAX_CONNECTIONS)
+}
+
+#[cfg(test)]
+mod tests {
+    use ::serve::*;
+    use ::search::tests::fixture;
+    use std::io::Read;
+
+    fn start_with(max_connections: usize) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = Arc::new(Server::new(fixture("")).unwrap());
+        thread::spawn(move || listen(server, listener, max_connections).unwrap());
+        addr
+    }
+
+    fn start() -> SocketAddr {
+        start_with(MAX_CONNECTIONS)
+    }
+
+    fn get(addr: SocketAddr, target: &str) -> (String, String) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", target).unwrap();
+        let mut resp = String::new();
+        stream.read_to_string(&mut resp).unwrap();
+        let mut parts = resp.splitn(2, "\r\n\r\n");
+        let head = parts.next().unwrap();
+        (head.lines().next().unwrap().to_owned(), parts.next().unwrap_or("").to_owned())
+    }
+
+    fn get_json(addr: SocketAddr, target: &str) -> (String, JsonValue) {
+        let (status, body) = get(addr, target);
+        (status, serde_json::from_str(&body).unwrap())
+    }
+
+    #[test]
+    fn endpoints() {
+        let addr = start();
+        let (status, res) = get_json(addr, "/search?q=rightwards+arrow&limit=1");
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert_eq!(res.as_array().unwrap().len(), 1);
+        assert_eq!(res[0]["char"], "→");
+        let (_, res) = get_json(addr, "/char/U+03BB");
+        assert_eq!(res["name"], "GREEK SMALL LETTER LAMDA");
+        let (_, res) = get_json(addr, "/blocks");
+        let arrows = res.as_array().unwrap().iter().find(|b| b["name"] == "Arrows").unwrap();
+        assert_eq!(*arrows, json!({ "name": "Arrows", "first": 0x2190, "last": 0x21ff, "count": 2 }));
+        assert_eq!(res.as_array().unwrap().iter().map(|b| b["count"].as_u64().unwrap()).collect::<Vec<_>>(), vec![2, 0, 2]);
+        // which is what the page searches for when a block is picked
+        let (_, res) = get_json(addr, "/search?q=codepnt%3A%5B8592+TO+8703%5D");
+        assert_eq!(res.as_array().unwrap().len(), 2);
+        let (status, body) = get(addr, "/");
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert!(body.contains("<html"));
+    }
+
+    #[test]
+    fn errors() {
+        let addr = start();
+        assert_eq!(get_json(addr, "/char/2191").0, "HTTP/1.1 404 Not Found");
+        assert_eq!(get_json(addr, "/char/zz").0, "HTTP/1.1 400 Bad Request");
+        let (status, res) = get_json(addr, "/search?q=left+%28arrow");
+        assert_eq!(status, "HTTP/1.1 400 Bad Request");
+        assert_eq!(res, json!({ "error": "a parenthesis that isn't closed", "position": 5, "literal": "left arrow" }));
+        assert_eq!(get_json(addr, "/nope").0, "HTTP/1.1 404 Not Found");
+        assert_eq!(get_json(addr, "/search?q=arrow&limit=18446744073709551615").0, "HTTP/1.1 400 Bad Request");
+        assert_eq!(get_json(addr, "/search?q=arrow&limit=1001").0, "HTTP/1.1 400 Bad Request");
+    }
+
+    #[test]
+    fn turns_away_too_many_connections() {
+        let addr = start_with(1);
+        // holds the only slot without sending anything
+        let idle = TcpStream::connect(addr).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        // which is answered before the request is read
+        let mut resp = String::new();
+        TcpStream::connect(addr).unwrap().read_to_string(&mut resp).unwrap();
+        assert!(resp.starts_with("HTTP/1.1 503 Service Unavailable"));
+        drop(idle);
+        // the slot is given back once the idle connection is hung up on
+        let status = (0..50).map(|_| { thread::sleep(Duration::from_millis(20)); get(addr, "/blocks").0 })
+            .find(|s| s == "HTTP/1.1 200 OK");
+        assert!(status.is_some());
+    }
+
+    #[test]
+    fn limits_requests() {
+        let status = |request: String| match read_head(&mut request.as_bytes()).unwrap() {
+            Ok(line) => line,
+            Err(resp) => resp.status.to_owned()
+        };
+        assert_eq!(status("GET / HTTP/1.1\r\nHost: localhost\r\n\r\n".to_owned()), "GET / HTTP/1.1\r\n");
+        let long = "x".repeat(MAX_LINE as usize);
+        assert_eq!(status(format!("GET /{} HTTP/1.1\r\n\r\n", long)), "400 Bad Request");
+        assert_eq!(status(format!("GET / HTTP/1.1\r\nX-Long: {}\r\n\r\n", long)), "431 Request Header Fields Too Large");
+        let headers = "X-Many: 1\r\n".repeat(MAX_HEADERS + 1);
+        assert_eq!(status(format!("GET / HTTP/1.1\r\n{}\r\n", headers)), "431 Request Header Fields Too Large");
+        assert_eq!(status(format!("GET / HTTP/1.1\r\n{}\r\n", &headers[11..])), "GET / HTTP/1.1\r\n");
+    }
+
+    #[test]
+    fn decoding() {
+        assert_eq!(url_decode("em+dash%20%E2%80%94"), "em dash —");
+        assert_eq!(url_decode("100%"), "100%");
+        assert_eq!(query_param("a=1&q=left%2Barrow", "q"), Some(String::from("left+arrow")));
+        assert_eq!(percent_decode("U+2192"), "U+2192");
+        assert_eq!(parse_codepoint("u+1F600"), Some(0x1f600));
+    }
+}
@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::{self, Read, Write};
+
+use toml::Value as TomlValue;
+
+use search::{Search, CharInfo};
+
+/// Words that start a different style when they follow a style's name prefix, ex. the ITALIC in
+/// MATHEMATICAL BOLD ITALIC CAPITAL A, which isn't just bold
+const STYLE_WORDS: [&str; 7] = ["BOLD", "ITALIC", "SCRIPT", "FRAKTUR", "DOUBLE-STRUCK", "SANS-SERIF", "MONOSPACE"];
+
+/// Where the characters of a style come from
+enum Source {
+    /// characters with this compatibility decomposition type to a single character, whose names start with one of the prefixes
+    Decomposition(&'static str, &'static [&'static str]),
+    /// LATIN LETTER SMALL CAPITAL A and friends, which have no decomposition
+    SmallCaps
+}
+
+pub struct Style {
+    pub name: &'static str,
+    source: Source
+}
+
+pub const STYLES: [Style; 19] = [
+    Style { name: "bold", source: Source::Decomposition("font", &["MATHEMATICAL BOLD"]) },
+    Style { name: "italic", source: Source::Decomposition("font", &["MATHEMATICAL ITALIC", "PLANCK CONSTANT"]) },
+    Style { name: "bold italic", source: Source::Decomposition("font", &["MATHEMATICAL BOLD ITALIC"]) },
+    Style { name: "script", source: Source::Decomposition("font", &["MATHEMATICAL SCRIPT", "SCRIPT"]) },
+    Style { name: "bold script", source: Source::Decomposition("font", &["MATHEMATICAL BOLD SCRIPT"]) },
+    Style { name: "fraktur", source: Source::Decomposition("font", &["MATHEMATICAL FRAKTUR", "BLACK-LETTER"]) },
+    Style { name: "bold fraktur", source: Source::Decomposition("font", &["MATHEMATICAL BOLD FRAKTUR"]) },
+    Style { name: "double-struck", source: Source::Decomposition("font", &["MATHEMATICAL DOUBLE-STRUCK", "DOUBLE-STRUCK"]) },
+    Style { name: "sans-serif", source: Source::Decomposition("font", &["MATHEMATICAL SANS-SERIF"]) },
+    Style { name: "sans-serif bold", source: Source::Decomposition("font", &["MATHEMATICAL SANS-SERIF BOLD"]) },
+    Style { name: "sans-serif italic", source: Source::Decomposition("font", &["MATHEMATICAL SANS-SERIF ITALIC"]) },
+    Style { name: "sans-serif bold italic", source: Source::Decomposition("font", &["MATHEMATICAL SANS-SERIF BOLD ITALIC"]) },
+    Style { name: "monospace", source: Source::Decomposition("font", &["MATHEMATICAL MONOSPACE"]) },
+    Style { name: "fullwidth", source: Source::Decomposition("wide", &["FULLWIDTH", "IDEOGRAPHIC SPACE"]) },
+    Style { name: "superscript", source: Source::Decomposition("super", &["SUPERSCRIPT", "MODIFIER LETTER"]) },
+    Style { name: "subscript", source: Source::Decomposition("sub", &["SUBSCRIPT", "LATIN SUBSCRIPT"]) },
+    Style { name: "circled", source: Source::Decomposition("circle", &["CIRCLED"]) },
+    Style { name: "squared", source: Source::Decomposition("square", &["SQUARED"]) },
+    Style { name: "small caps", source: Source::SmallCaps }
+];
+
+/// Whether `name` starts with one of `prefixes` and isn't a more specific style
+fn has_prefix(name: &str, prefixes: &[&str]) -> bool {
+    prefixes.iter().any(|p| match name.strip_prefix(p) {
+        Some("") => true,
+        Some(rest) => rest.strip_prefix(' ').is_some_and(|rest| !STYLE_WORDS.iter().any(|w| rest.split(' ').next() == Some(w))),
+        None => false
+    })
+}
+
+impl Style {
+    /// The plain characters `c` is a styled version of
+    fn bases(&self, c: &CharInfo) -> Vec<char> {
+        match self.source {
+            Source::Decomposition(tag, prefixes) => {
+                let mut dm = c.decomposition.chars();
+                match (dm.next(), dm.next()) {
+                    (Some(base), None) if c.decomposition_type == tag && has_prefix(&c.name, prefixes) => vec![base],
+                    _ => Vec::new()
+                }
+            },
+            Source::SmallCaps => match c.name.strip_prefix("LATIN LETTER SMALL CAPITAL ") {
+                Some(l) if l.len() == 1 && l.chars().all(|l| l.is_ascii_uppercase()) => l.chars().flat_map(|l| vec![l, l.to_ascii_lowercase()]).collect(),
+                _ => Vec::new()
+            }
+        }
+    }
+}
+
+/// Maps from plain characters to their styled versions, built from the index
+pub struct Styles {
+    maps: Vec<HashMap<char, char>>
+}
+
+impl Styles {
+    pub fn from_chars(chars: &[CharInfo]) -> Styles {
+        let mut maps = vec![HashMap::new(); STYLES.len()];
+        for c in chars {
+            let styled = match c.chr() { Some(s) => s, None => continue };
+            for (style, map) in STYLES.iter().zip(maps.iter_mut()) {
+                for base in style.bases(c) {
+                    // the first in codepoint order wins, which prefers the older, better supported characters
+                    map.entry(base).or_insert(styled);
+                }
+            }
+        }
+        Styles { maps }
+    }
+
+    pub fn open(search: &Search) -> Result<Styles, Box<dyn Error>> {
+        Ok(Styles::from_chars(&search.all_chars()?))
+    }
+
+    /// Convert `text` to the style at `style` in STYLES, leaving the characters it has no version of alone
+    pub fn apply(&self, style: usize, text: &str) -> String {
+        text.chars().map(|c| self.maps[style].get(&c).cloned().unwrap_or(c)).collect()
+    }
+
+    /// `text` in every style that changes it
+    pub fn all(&self, text: &str) -> Vec<(&'static str, String)> {
+        STYLES.iter().enumerate()
+            .map(|(i, s)| (s.name, self.apply(i, text)))
+            .filter(|(_, styled)| styled != text)
+            .collect()
+    }
+}
+
+/// `ununi style [--style NAME] [TEXT...]` prints the text (or stdin) in every style, or just one
+pub fn run(config: &Option<TomlValue>, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut style = None;
+    let mut words = Vec::new();
+    let mut args = args.iter();
+    while let Some(a) = args.next() {
+        match a.as_str() {
+            "--style" => {
+                let name = args.next().ok_or("--style needs a style name")?;
+                style = Some(STYLES.iter().position(|s| s.name.eq_ignore_ascii_case(name)).ok_or_else(|| format!("unknown style {}; styles are: {}",
+                    name, STYLES.iter().map(|s| s.name).collect::<Vec<_>>().join(", ")))?);
+            },
+            _ => words.push(a.as_str())
+        }
+    }
+    let text = if words.is_empty() {
+        let mut text = String::new();
+        io::stdin().read_to_string(&mut text)?;
+        text
+    } else {
+        words.join(" ")
+    };
+
+    let styles = Styles::open(&Search::open(config)?)?;
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    match style {
+        Some(style) => write!(out, "{}", styles.apply(style, &text))?,
+        None => for (name, styled) in styles.all(&text) {
+            writeln!(out, "{:<22} {}", name, styled)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ::styles::*;
+    use ::search::tests::search_from_xml;
+
+    const FIXTURE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<ucd xmlns="http://www.unicode.org/ns/2003/ucd/1.0">
+<repertoire>
+<group blk="Latin_1_Sup" gc="Lo" dt="super">
+<char cp="00AA" na="FEMININE ORDINAL INDICATOR" dm="0061"/>
+</group>
+<group blk="Letterlike_Symbols" gc="Ll" dt="font">
+<char cp="210E" na="PLANCK CONSTANT" dm="0068"/>
+<char cp="2124" na="DOUBLE-STRUCK CAPITAL Z" gc="Lu" dm="005A"/>
+</group>
+<group blk="Phonetic_Ext" gc="Ll">
+<char cp="1D00" na="LATIN LETTER SMALL CAPITAL A"/>
+<char cp="1D43" na="MODIFIER LETTER SMALL A" gc="Lm" dt="super" dm="0061"/>
+</group>
+<group blk="Half_And_Full_Forms" gc="Ll" dt="wide">
+<char cp="FF41" na="FULLWIDTH LATIN SMALL LETTER A" dm="0061"/>
+</group>
+<group blk="Math_Alphanum" gc="Lu" dt="font">
+<char cp="1D400" na="MATHEMATICAL BOLD CAPITAL A" dm="0041"/>
+<char cp="1D41B" na="MATHEMATICAL BOLD SMALL B" gc="Ll" dm="0062"/>
+<char cp="1D468" na="MATHEMATICAL BOLD ITALIC CAPITAL A" dm="0041"/>
+<char cp="1D434" na="MATHEMATICAL ITALIC CAPITAL A" dm="0041"/>
+<char cp="1D538" na="MATHEMATICAL DOUBLE-STRUCK CAPITAL A" dm="0041"/>
+</group>
+</repertoire>
+</ucd>
+"#;
+
+    #[test]
+    fn styles_from_decompositions() {
+        let s = search_from_xml(FIXTURE);
+        let styles = Styles::open(&s).unwrap();
+        let style = |name| STYLES.iter().position(|s| s.name == name).unwrap();
+        assert_eq!(styles.apply(style("bold"), "Ab!"), "\u{1d400}\u{1d41b}!");
+        assert_eq!(styles.apply(style("bold italic"), "A"), "\u{1d468}");
+        // holes in the mathematical alphabets are filled from the letterlike symbols
+        assert_eq!(styles.apply(style("italic"), "Ah"), "\u{1d434}\u{210e}");
+        assert_eq!(styles.apply(style("double-struck"), "AZ"), "\u{1d538}\u{2124}");
+        assert_eq!(styles.apply(style("superscript"), "a"), "\u{1d43}");
+        assert_eq!(styles.apply(style("fullwidth"), "a"), "\u{ff41}");
+        assert_eq!(styles.apply(style("small caps"), "aA"), "\u{1d00}\u{1d00}");
+        let all = styles.all("a");
+        assert_eq!(all.iter().map(|(n, _)| *n).collect::<Vec<_>>(), vec!["fullwidth", "superscript", "small caps"]);
+    }
+}
@@ -0,0 +1,144 @@
+use std::error::Error;
+
+use toml::Value as TomlValue;
+
+use search::{Search, last_word};
+use answer::answer;
+
+/// How the last word of `query` most likely goes on, as the text to add after it: the rest of the word of names,
+/// blocks and aliases starting with it that the most characters have. None when the query doesn't end in a word,
+/// or the word is already whole and nothing longer is more common.
+pub fn complete(search: &Search, query: &str) -> Result<Option<String>, Box<dyn Error>> {
+    let last = match last_word(query) { Some(w) => w.to_lowercase(), None => return Ok(None) };
+    let mut words = search.vocabulary(&last)?;
+    // the most common first, then the shortest, then in order
+    words.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.len().cmp(&b.0.len())));
+    Ok(words.into_iter().next().filter(|w| w.0 != last).map(|w| w.0[last.len()..].to_owned()))
+}
+
+/// The number of insertions, deletions, substitutions and swaps of neighbouring characters that turn `a` into `b`
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b) = (a.chars().collect::<Vec<_>>(), b.chars().collect::<Vec<_>>());
+    // three rows of the table are all that is ever looked at
+    let mut before = vec![0; b.len() + 1];
+    let mut prev = (0..=b.len()).collect::<Vec<_>>();
+    let mut row = vec![0; b.len() + 1];
+    for i in 1..=a.len() {
+        row[0] = i;
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            row[j] = (prev[j] + 1).min(row[j - 1] + 1).min(prev[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(before[j - 2] + 1);
+            }
+        }
+        ::std::mem::swap(&mut before, &mut prev);
+        ::std::mem::swap(&mut prev, &mut row);
+    }
+    prev[b.len()]
+}
+
+/// How far off a word of `len` characters can be for a correction to still be likely what was meant
+fn max_distance(len: usize) -> u8 {
+    match len { 0..=2 => 0, 3..=4 => 1, _ => 2 }
+}
+
+/// The most corrected queries that are run to see whether they find anything, so a query with many unknown words
+/// that have many close ones doesn't take long to give up on
+const MAX_TRIES: usize = 10;
+
+/// Queries like `query` with the words that no name, block or alias has replaced by ones close to them that some do,
+/// at most `limit` of them and only ones that find something. Field queries and operators are kept as they are.
+pub fn did_you_mean(search: &Search, query: &str, limit: usize) -> Result<Vec<String>, Box<dyn Error>> {
+    let tokens = query.split_whitespace().collect::<Vec<_>>();
+    let mut corrections = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        let word = token.to_lowercase();
+        if !token.chars().all(char::is_alphanumeric) || ["AND", "OR", "NOT"].contains(token) || search.word_count(&word) > 0 {
+            continue;
+        }
+        let mut close = search.close_words(&word, max_distance(word.chars().count()))?.into_iter()
+            .map(|(v, n)| (edit_distance(&word, &v), n, v))
+            .collect::<Vec<_>>();
+        // the closest first, then the most common
+        close.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)));
+        if !close.is_empty() {
+            corrections.push((i, close.into_iter().map(|c| c.2).collect::<Vec<_>>()));
+        }
+    }
+    let mut suggestions: Vec<String> = Vec::new();
+    // the n-th suggestion takes the n-th closest word for each unknown one, as long as there are that many
+    let most = corrections.iter().map(|c| c.1.len()).max().unwrap_or(0);
+    for n in 0..most.min(MAX_TRIES) {
+        if suggestions.len() == limit { break; }
+        let mut words = tokens.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+        for (i, close) in &corrections {
+            words[*i] = close[n.min(close.len() - 1)].clone();
+        }
+        let suggestion = words.join(" ");
+        if !suggestions.contains(&suggestion) && search.query_page(&suggestion, 0, 1)?.total > 0 {
+            suggestions.push(suggestion);
+        }
+    }
+    Ok(suggestions)
+}
+
+/// `ununi suggest QUERY...` prints how the last word of the query would be completed, and the corrected queries
+/// offered when it finds nothing
+pub fn run(config: &Option<TomlValue>, args: &[String]) -> Result<(), Box<dyn Error>> {
+    if args.is_empty() { return Err("usage: ununi suggest QUERY...".into()); }
+    let query = args.join(" ");
+    let search = Search::open(config)?;
+    let answer = answer(&search, &query, 1, true)?;
+    if let Some(rest) = answer.completion {
+        println!("{}{}", query, rest);
+    }
+    if answer.total == 0 {
+        for suggestion in did_you_mean(&search, &query, 5)? {
+            println!("did you mean: {}", suggestion);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ::suggest::*;
+    use ::search::tests::fixture;
+
+    #[test]
+    fn distances() {
+        assert_eq!(edit_distance("arrow", "arrow"), 0);
+        assert_eq!(edit_distance("arow", "arrow"), 1);
+        assert_eq!(edit_distance("arw", "arrow"), 2);
+        assert_eq!(edit_distance("lmada", "lamda"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn completes_last_word() {
+        let s = fixture("");
+        assert_eq!(complete(&s, "leftwards ar").unwrap(), Some("row".to_owned()));
+        // the most common word wins, `letter` is in more names than `left...`
+        assert_eq!(complete(&s, "Le").unwrap(), Some("tter".to_owned()));
+        assert_eq!(complete(&s, "arrow").unwrap(), None);
+        assert_eq!(complete(&s, "arrow ").unwrap(), None);
+        assert_eq!(complete(&s, "leftwards\u{3000}ar").unwrap(), Some("row".to_owned()));
+        assert_eq!(complete(&s, "arrow\u{a0}").unwrap(), None);
+        assert_eq!(complete(&s, "blck:ar").unwrap(), None);
+        assert_eq!(complete(&s, "xyz").unwrap(), None);
+    }
+
+    #[test]
+    fn suggests_corrections() {
+        let s = fixture("");
+        assert_eq!(did_you_mean(&s, "leftwrds arrw", 3).unwrap(), vec!["leftwards arrow"]);
+        assert_eq!(did_you_mean(&s, "grek", 3).unwrap(), vec!["greek"]);
+        assert!(did_you_mean(&s, "arrow", 3).unwrap().is_empty());
+        assert!(did_you_mean(&s, "qqqqqqq", 3).unwrap().is_empty());
+        assert_eq!((s.word_count("ARROW"), s.word_count("arrows"), s.word_count("arow")), (2, 2, 0));
+        assert_eq!(s.close_words("arow", 1).unwrap(), vec![("arrow".to_owned(), 2)]);
+        assert_eq!(s.close_words("arow", 2).unwrap(), vec![("arrow".to_owned(), 2), ("arrows".to_owned(), 2)]);
+        assert!(s.close_words("arow", 0).unwrap().is_empty());
+    }
+}